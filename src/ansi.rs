@@ -0,0 +1,313 @@
+use crate::{
+    span::{Span, SpanStyle},
+    style::Color,
+};
+
+const ESC: char = '\x1b';
+const BEL: char = '\x07';
+
+/// Piece of a pre-colored string.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token<'a> {
+    Text(&'a str),
+    /// Parameters of a `ESC [ ... m` sequence.
+    Sgr(Vec<u16>),
+    /// Any other escape sequence, it has no visible width.
+    Escape(&'a str),
+}
+
+/// Splits a string into text and escape sequences.
+///
+/// Recognises CSI (`ESC [`), OSC (`ESC ]`, terminated by BEL or `ESC \`),
+/// escapes with intermediate bytes and two character escapes, an unterminated
+/// sequence is swallowed.
+pub fn tokenize(input: &str) -> Vec<Token<'_>> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut rest = input;
+
+    while !rest.is_empty() {
+        let Some(start) = rest.find(ESC) else {
+            tokens.push(Token::Text(rest));
+            break;
+        };
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+
+        let sequence = &rest[start..];
+        let end = sequence_len(sequence);
+        let escape = &sequence[..end];
+
+        match escape.strip_prefix("\x1b[").and_then(|s| s.strip_suffix('m')) {
+            Some(params) => tokens.push(Token::Sgr(parse_params(params))),
+            None => tokens.push(Token::Escape(escape)),
+        }
+        rest = &sequence[end..];
+    }
+
+    tokens
+}
+
+/// Converts a pre-colored string into styled spans.
+pub fn parse(input: &str) -> Vec<Span> {
+    parse_with(input, &SpanStyle::default())
+}
+
+/// Like `parse`, starting from `base` and returning to it on `ESC [ 0 m`.
+pub fn parse_with(input: &str, base: &SpanStyle) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    let mut style = base.clone();
+
+    for token in tokenize(input) {
+        match token {
+            Token::Text(text) => match spans.last_mut() {
+                Some(last) if last.style == style => last.text.push_str(text),
+                _ => spans.push(Span::styled(text, style.clone())),
+            },
+            Token::Sgr(params) => apply_sgr(&mut style, base, &params),
+            Token::Escape(_) => {},
+        }
+    }
+
+    spans
+}
+
+/// Removes every escape sequence, keeping only visible text.
+pub fn strip(input: &str) -> String {
+    tokenize(input)
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::Text(text) => Some(text),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Visible width of a pre-colored string in terminal cells.
+pub fn width(input: &str) -> usize {
+    tokenize(input)
+        .into_iter()
+        .map(|token| match token {
+            Token::Text(text) => crate::text::width(text),
+            _ => 0,
+        })
+        .sum()
+}
+
+fn sequence_len(sequence: &str) -> usize {
+    let bytes = sequence.as_bytes();
+    match bytes.get(1) {
+        None => 1,
+        Some(b'[') => bytes[2..]
+            .iter()
+            .position(|b| (0x40..=0x7e).contains(b))
+            .map(|i| i + 3)
+            .unwrap_or(bytes.len()),
+        Some(b']') => {
+            let body = &sequence[2..];
+            match (body.find(BEL), body.find("\x1b\\")) {
+                (Some(bel), Some(st)) if st < bel => st + 4,
+                (Some(bel), _) => bel + 3,
+                (None, Some(st)) => st + 4,
+                (None, None) => sequence.len(),
+            }
+        },
+        // intermediate bytes, e.g. the charset designation `ESC ( B`
+        Some(0x20..=0x2f) => bytes[1..]
+            .iter()
+            .position(|b| (0x30..=0x7e).contains(b))
+            .map(|i| i + 2)
+            .unwrap_or(bytes.len()),
+        Some(_) => 1 + sequence[1..].chars().next().map(char::len_utf8).unwrap_or(0),
+    }
+}
+
+/// Splits SGR parameters. Empty ones mean `0`, values that do not fit are kept as
+/// `u16::MAX` so they are ignored instead of resetting the style.
+///
+/// Colon sub-parameters (`38:2::r:g:b`, `4:3`) are flattened to their `;` form.
+fn parse_params(params: &str) -> Vec<u16> {
+    if params.is_empty() {
+        return vec![0];
+    }
+    let value = |p: &str| if p.is_empty() {0} else {p.parse::<u16>().unwrap_or(u16::MAX)};
+
+    params
+        .split(';')
+        .flat_map(|group| {
+            let parts: Vec<u16> = group.split(':').map(value).collect();
+            match parts.as_slice() {
+                // `38:2:colorspace:r:g:b`, the colorspace id is dropped
+                [code @ (38 | 48), 2, _, r, g, b, ..] => vec![*code, 2, *r, *g, *b],
+                [38 | 48, ..] => parts,
+                // `4:0` turns underline off, other underline styles are plain underline
+                [4, 0] => vec![24],
+                [code, ..] => vec![*code],
+                [] => vec![],
+            }
+        })
+        .collect()
+}
+
+fn apply_sgr(style: &mut SpanStyle, base: &SpanStyle, params: &[u16]) {
+    let mut i = 0;
+    while i < params.len() {
+        match params[i] {
+            0 => *style = base.clone(),
+            1 => style.bold = true,
+            2 => style.dim = true,
+            3 => style.italic = true,
+            4 => style.underline = true,
+            5 | 6 => style.blink = true,
+            7 => style.reverse = true,
+            8 => style.hidden = true,
+            9 => style.strikethrough = true,
+            21 | 22 => {
+                style.bold = false;
+                style.dim = false;
+            },
+            23 => style.italic = false,
+            24 => style.underline = false,
+            25 => style.blink = false,
+            27 => style.reverse = false,
+            28 => style.hidden = false,
+            29 => style.strikethrough = false,
+            v @ 30..=37 => style.foreground = Some(basic_color(v - 30, false)),
+            v @ 90..=97 => style.foreground = Some(basic_color(v - 90, true)),
+            v @ 40..=47 => style.background = Some(basic_color(v - 40, false)),
+            v @ 100..=107 => style.background = Some(basic_color(v - 100, true)),
+            39 => style.foreground = base.foreground.clone(),
+            49 => style.background = base.background.clone(),
+            38 | 48 => {
+                let (color, used) = extended_color(&params[i + 1..]);
+                if let Some(color) = color {
+                    if params[i] == 38 {
+                        style.foreground = Some(color);
+                    } else {
+                        style.background = Some(color);
+                    }
+                }
+                i += used;
+            },
+            _ => {},
+        }
+        i += 1;
+    }
+}
+
+/// Parses `5;n` or `2;r;g;b`, returning the color and the number of consumed params.
+/// Out of range values are consumed without changing the color.
+fn extended_color(params: &[u16]) -> (Option<Color>, usize) {
+    let byte = |v: &u16| u8::try_from(*v).ok();
+    match params {
+        [5, n, ..] => (byte(n).map(Color::Ansi), 2),
+        [2, r, g, b, ..] => match (byte(r), byte(g), byte(b)) {
+            (Some(r), Some(g), Some(b)) => (Some(Color::Rgb(r, g, b)), 4),
+            _ => (None, 4),
+        },
+        _ => (None, params.len()),
+    }
+}
+
+fn basic_color(index: u16, bright: bool) -> Color {
+    match (index, bright) {
+        (0, false) => Color::Black,
+        (1, false) => Color::Red,
+        (2, false) => Color::Green,
+        (3, false) => Color::Yellow,
+        (4, false) => Color::Blue,
+        (5, false) => Color::Magenta,
+        (6, false) => Color::Cyan,
+        (7, false) => Color::White,
+        (0, true) => Color::BrightBlack,
+        (1, true) => Color::BrightRed,
+        (2, true) => Color::BrightGreen,
+        (3, true) => Color::BrightYellow,
+        (4, true) => Color::BrightBlue,
+        (5, true) => Color::BrightMagenta,
+        (6, true) => Color::BrightCyan,
+        _ => Color::BrightWhite,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn style_of(input: &str) -> SpanStyle {
+        parse(input).last().map(|span| span.style.clone()).unwrap_or_default()
+    }
+
+    #[test]
+    fn basic_and_bright_colors() {
+        let spans = parse("a\x1b[31mb\x1b[1;94mc\x1b[0md");
+
+        assert_eq!(spans, vec![
+            Span::raw("a"),
+            Span::styled("b", SpanStyle::new().fg(Color::Red)),
+            Span::styled("c", SpanStyle::new().fg(Color::BrightBlue).bold()),
+            Span::raw("d"),
+        ]);
+    }
+
+    #[test]
+    fn extended_colors() {
+        assert_eq!(style_of("\x1b[38;5;208mx"), SpanStyle::new().fg(Color::Ansi(208)));
+        assert_eq!(style_of("\x1b[48;2;10;20;30mx"), SpanStyle::new().bg(Color::Rgb(10, 20, 30)));
+        assert_eq!(
+            style_of("\x1b[38;2;1;2;3;4mx"),
+            SpanStyle::new().fg(Color::Rgb(1, 2, 3)).underline(),
+        );
+    }
+
+    #[test]
+    fn colon_parameters() {
+        assert_eq!(style_of("\x1b[38:5:99mx"), SpanStyle::new().fg(Color::Ansi(99)));
+        assert_eq!(style_of("\x1b[38:2:1:2:3mx"), SpanStyle::new().fg(Color::Rgb(1, 2, 3)));
+        assert_eq!(style_of("\x1b[38:2::1:2:3mx"), SpanStyle::new().fg(Color::Rgb(1, 2, 3)));
+        assert_eq!(style_of("\x1b[4:3mx"), SpanStyle::new().underline());
+        assert_eq!(style_of("\x1b[4m\x1b[4:0mx"), SpanStyle::new());
+    }
+
+    #[test]
+    fn overflowing_parameters_are_ignored() {
+        assert_eq!(style_of("\x1b[1m\x1b[99999mx"), SpanStyle::new().bold());
+        assert_eq!(style_of("\x1b[38;5;300mx"), SpanStyle::new());
+        assert_eq!(style_of("\x1b[38;2;1;256;3;1mx"), SpanStyle::new().bold());
+    }
+
+    #[test]
+    fn empty_parameters_reset() {
+        assert_eq!(style_of("\x1b[1m\x1b[mx"), SpanStyle::new());
+        assert_eq!(style_of("\x1b[31;;1mx"), SpanStyle::new().bold());
+    }
+
+    #[test]
+    fn reset_returns_to_the_base_style() {
+        let base = SpanStyle::new().fg(Color::Green);
+        let spans = parse_with("\x1b[31ma\x1b[0mb\x1b[34;39mc", &base);
+
+        assert_eq!(spans[1], Span::styled("bc", base));
+    }
+
+    #[test]
+    fn unterminated_csi_is_swallowed() {
+        assert_eq!(tokenize("ab\x1b[31"), vec![Token::Text("ab"), Token::Escape("\x1b[31")]);
+        assert_eq!(strip("ab\x1b["), "ab");
+        assert_eq!(strip("ab\x1b"), "ab");
+    }
+
+    #[test]
+    fn osc_8_hyperlinks() {
+        let input = "see \x1b]8;;https://example.com\x1b\\link\x1b]8;;\x07 here";
+
+        assert_eq!(strip(input), "see link here");
+        assert_eq!(width(input), 13);
+        assert_eq!(tokenize(input)[1], Token::Escape("\x1b]8;;https://example.com\x1b\\"));
+    }
+
+    #[test]
+    fn non_sgr_sequences_have_no_width() {
+        assert_eq!(width("\x1b[2J\x1b[1;1H\x1b(B日本"), 4);
+    }
+}
@@ -1,11 +1,103 @@
-use std::sync::mpsc::{self, Receiver};
-use crate::layout::{Container, StyleLink};
-
 pub trait InputListenerTrait {
 }
 
+/// Modifier keys held down together with a key, combined with `|`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub const NONE: Modifiers = Modifiers(0);
+    pub const SHIFT: Modifiers = Modifiers(1);
+    pub const CONTROL: Modifiers = Modifiers(1 << 1);
+    pub const ALT: Modifiers = Modifiers(1 << 2);
+    pub const SUPER: Modifiers = Modifiers(1 << 3);
+    pub const HYPER: Modifiers = Modifiers(1 << 4);
+    pub const META: Modifiers = Modifiers(1 << 5);
+
+    pub fn contains(&self, other: Modifiers) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn insert(&mut self, other: Modifiers) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: Modifiers) {
+        self.0 &= !other.0;
+    }
+}
+
+impl std::ops::BitOr for Modifiers {
+    type Output = Modifiers;
+
+    fn bitor(self, other: Modifiers) -> Modifiers {
+        Modifiers(self.0 | other.0)
+    }
+}
+
+/// A key together with its modifiers, e.g. `Key::new(KeyCode::Char('s')).ctrl()` for Ctrl+S.
+///
+/// Shift is not part of `Char` and `BackTab` keys, it is already in the key itself (`'S'`, `'!'`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Key {
     pub keycode: KeyCode,
+    pub modifiers: Modifiers,
+}
+
+impl Key {
+    pub fn new(keycode: KeyCode) -> Self {
+        Key {
+            keycode,
+            modifiers: Modifiers::NONE,
+        }
+    }
+
+    pub fn with_modifiers(keycode: KeyCode, modifiers: Modifiers) -> Self {
+        Key {
+            keycode,
+            modifiers,
+        }
+    }
+
+    pub fn shift(mut self) -> Self {
+        self.modifiers.insert(Modifiers::SHIFT);
+        self
+    }
+
+    pub fn ctrl(mut self) -> Self {
+        self.modifiers.insert(Modifiers::CONTROL);
+        self
+    }
+
+    pub fn alt(mut self) -> Self {
+        self.modifiers.insert(Modifiers::ALT);
+        self
+    }
+
+    pub fn super_key(mut self) -> Self {
+        self.modifiers.insert(Modifiers::SUPER);
+        self
+    }
+
+    pub fn hyper(mut self) -> Self {
+        self.modifiers.insert(Modifiers::HYPER);
+        self
+    }
+
+    pub fn meta(mut self) -> Self {
+        self.modifiers.insert(Modifiers::META);
+        self
+    }
+}
+
+impl From<KeyCode> for Key {
+    fn from(keycode: KeyCode) -> Self {
+        Key::new(keycode)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -37,6 +129,16 @@ pub enum KeyCode {
     KeypadBegin,
 }
 
+/// Identifies a registered callback, so it can be removed again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HandlerId(u64);
+
+impl HandlerId {
+    pub(crate) fn new(id: u64) -> Self {
+        HandlerId(id)
+    }
+}
+
 pub enum InputAction {
     Press,
     Hold,
@@ -45,6 +147,7 @@ pub enum InputAction {
 
 pub struct Input {
     pub key_code: KeyCode,
+    pub modifiers: Modifiers,
     pub action: InputAction,
 }
 
@@ -52,7 +155,74 @@ impl Input {
     pub fn new(key_code: KeyCode, action: InputAction) -> Self {
         Input {
             key_code,
+            modifiers: Modifiers::NONE,
             action,
         }
     }
-}
\ No newline at end of file
+
+    pub fn with_modifiers(key_code: KeyCode, modifiers: Modifiers, action: InputAction) -> Self {
+        Input {
+            key_code,
+            modifiers,
+            action,
+        }
+    }
+
+    pub fn key(&self) -> Key {
+        Key::with_modifiers(self.key_code, self.modifiers)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseAction {
+    Down(MouseButton),
+    Up(MouseButton),
+    Drag(MouseButton),
+    Move,
+    ScrollUp,
+    ScrollDown,
+    ScrollLeft,
+    ScrollRight,
+}
+
+/// A mouse event at a terminal cell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MouseEvent {
+    pub action: MouseAction,
+    pub column: u16,
+    pub row: u16,
+    pub modifiers: Modifiers,
+    /// Id of the deepest container under the cursor, filled in by `Rll::hit_test`.
+    pub target: Option<String>,
+}
+
+impl MouseEvent {
+    pub fn new(action: MouseAction, column: u16, row: u16, modifiers: Modifiers) -> Self {
+        MouseEvent {
+            action,
+            column,
+            row,
+            modifiers,
+            target: None,
+        }
+    }
+}
+
+/// Everything a listener reports back to the application.
+pub enum Event {
+    Input(Input),
+    Mouse(MouseEvent),
+    /// The terminal was resized to `(columns, rows)`.
+    Resize(u16, u16),
+    /// A container gained focus, emitted by `Rll` and not by listeners.
+    Focus(String),
+    /// A container lost focus, emitted by `Rll` and not by listeners.
+    Blur(String),
+}
@@ -1,46 +1,23 @@
-use std::io::stdout;
-
-use crossterm::{
-    cursor::{
-        Hide, 
-        MoveTo, 
-        Show
-    }, 
-    execute, 
-    style::Print, 
-    terminal::{
-        self, 
-        disable_raw_mode, 
-        enable_raw_mode, 
-        Clear, 
-        ClearType, 
-        EnterAlternateScreen, 
-        LeaveAlternateScreen
-    }, 
-    ExecutableCommand,
+use std::io;
+
+use crossterm::terminal::{
+    disable_raw_mode, 
+    enable_raw_mode, 
 };
 
 use crate::backends::traits::{BackendTrait, DrawerTrair, ListenerTrait};
-use crate::{
-    layout::{
-        Container, 
-        StyleLink
-    }, 
-    style::{
-        Style,
-        Align, 
-        ContentWrap, 
-        Orientation, 
-        Size, 
-    }
+use crate::layout::{
+    Container, 
+    Dirty, 
+    StyleIndex
 };
 
-use super::{drawer::Drawer, listener::{self, Listener}};
-
 
 pub struct Backend<D: DrawerTrair, L: ListenerTrait> {
     pub drawer: D,
     pub listener: L,
+    mouse_capture: bool,
+    keyboard_enhancement: bool,
 }
 
 impl<D: DrawerTrair, L: ListenerTrait> Backend<D, L> {
@@ -48,29 +25,74 @@ impl<D: DrawerTrair, L: ListenerTrait> Backend<D, L> {
         Backend {
             drawer,
             listener,
+            mouse_capture: false,
+            keyboard_enhancement: false,
         }
     }
+
+    /// Reports mouse events, disabled by default. With capture on, the terminal's own
+    /// text selection usually needs Shift held.
+    pub fn with_mouse_capture(mut self, enabled: bool) -> Self {
+        self.mouse_capture = enabled;
+        self
+    }
+
+    /// Reports key repeats and releases, disabled by default. Needs a terminal with the
+    /// kitty keyboard protocol, others keep sending presses only.
+    pub fn with_keyboard_enhancement(mut self, enabled: bool) -> Self {
+        self.keyboard_enhancement = enabled;
+        self
+    }
 }
 
 impl<D: DrawerTrair, L: ListenerTrait> BackendTrait for Backend<D, L> {
-    fn init(&mut self) {
-        let _ = enable_raw_mode();
+    fn init(&mut self) -> io::Result<()> {
+        enable_raw_mode()?;
+        self.drawer.set_mouse_capture(self.mouse_capture);
+        self.drawer.set_keyboard_enhancement(self.keyboard_enhancement);
+        // the drawer may query the terminal, which has to happen before the input thread reads it
+        if let Err(error) = self.drawer.init() {
+            let _ = disable_raw_mode();
+            return Err(error);
+        }
         self.listener.watch_input();
-        let _ = execute!(stdout(), EnterAlternateScreen);
+        Ok(())
     }
 
-    fn close(& self) {
+    fn close(&mut self) {
         let _ = disable_raw_mode();
-        let _ = execute!(stdout(), LeaveAlternateScreen);
-        let mut stdout = stdout();
-        stdout.execute(Show);
+        self.drawer.close();
     }
 
-    fn render(&mut self, main_container: Container, style_links: Vec<StyleLink>) {
-        self.drawer.render(main_container, style_links);
+    fn render(&mut self, main_container: &Container, styles: &StyleIndex, dirty: &Dirty) {
+        self.drawer.render(main_container, styles, dirty);
     }
 
-    fn display(&self) {
+    fn display(&mut self) {
         self.drawer.display();
     }
+
+    fn needs_display(&self) -> bool {
+        self.drawer.needs_display()
+    }
+
+    fn invalidate(&mut self) {
+        self.drawer.invalidate();
+    }
+
+    fn containers_at(&self, column: u16, row: u16) -> Vec<String> {
+        self.drawer.containers_at(column, row)
+    }
+
+    fn rect_of(&self, id: &str) -> Option<(u32, u32, u32, u32)> {
+        self.drawer.rect_of(id)
+    }
+
+    fn tick(&mut self) {
+        self.listener.tick();
+    }
+
+    fn mode(&self) -> Option<String> {
+        self.listener.mode()
+    }
 }
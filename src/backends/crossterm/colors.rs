@@ -1,3 +1,7 @@
+use crossterm::style::{Attribute, Attributes};
+
+use crate::{span::SpanStyle, style::Color};
+
 pub const RESET: &str = "\x1b[0m";
 
 pub const BLACK: &str = "\x1b[30m";
@@ -89,11 +93,46 @@ pub const ALL_COLORS_AND_STYLES: [&str; 41] = [
     STRIKETHROUGH,
 ];
 
-pub fn remove_colors_from_len(mut len: usize, string: &str) -> usize {
-    for color in ALL_COLORS_AND_STYLES {
-        if string.contains(color) {
-            len -= color.len()
+pub fn to_crossterm_color(color: &Color) -> crossterm::style::Color {
+    match color {
+        Color::Reset => crossterm::style::Color::Reset,
+        Color::Black => crossterm::style::Color::Black,
+        Color::Red => crossterm::style::Color::DarkRed,
+        Color::Green => crossterm::style::Color::DarkGreen,
+        Color::Yellow => crossterm::style::Color::DarkYellow,
+        Color::Blue => crossterm::style::Color::DarkBlue,
+        Color::Magenta => crossterm::style::Color::DarkMagenta,
+        Color::Cyan => crossterm::style::Color::DarkCyan,
+        Color::White => crossterm::style::Color::Grey,
+        Color::BrightBlack => crossterm::style::Color::DarkGrey,
+        Color::BrightRed => crossterm::style::Color::Red,
+        Color::BrightGreen => crossterm::style::Color::Green,
+        Color::BrightYellow => crossterm::style::Color::Yellow,
+        Color::BrightBlue => crossterm::style::Color::Blue,
+        Color::BrightMagenta => crossterm::style::Color::Magenta,
+        Color::BrightCyan => crossterm::style::Color::Cyan,
+        Color::BrightWhite => crossterm::style::Color::White,
+        Color::Ansi(v) => crossterm::style::Color::AnsiValue(*v),
+        Color::Rgb(r, g, b) => crossterm::style::Color::Rgb { r: *r, g: *g, b: *b },
+        Color::Var(_) => crossterm::style::Color::Reset,
+    }
+}
+
+pub fn to_crossterm_attributes(style: &SpanStyle) -> Attributes {
+    let mut attributes = Attributes::default();
+    for (enabled, attribute) in [
+        (style.bold, Attribute::Bold),
+        (style.dim, Attribute::Dim),
+        (style.italic, Attribute::Italic),
+        (style.underline, Attribute::Underlined),
+        (style.blink, Attribute::SlowBlink),
+        (style.reverse, Attribute::Reverse),
+        (style.hidden, Attribute::Hidden),
+        (style.strikethrough, Attribute::CrossedOut),
+    ] {
+        if enabled {
+            attributes.set(attribute);
         }
-    };
-    len
-}
\ No newline at end of file
+    }
+    attributes
+}
@@ -1,105 +1,564 @@
-use std::io::stdout;
+use std::{io::{self, stdout, Stdout, Write}, time::{SystemTime, UNIX_EPOCH}};
 
 use crossterm::{
     cursor::{
+        self as term_cursor, 
         Hide, 
         MoveTo, 
-        Show
+        Show, 
+    }, 
+    event::{
+        DisableMouseCapture, 
+        EnableMouseCapture, 
+        KeyboardEnhancementFlags, 
+        PopKeyboardEnhancementFlags, 
+        PushKeyboardEnhancementFlags, 
+    }, 
+    queue, 
+    style::{
+        Attribute, 
+        Print, 
+        SetAttribute, 
+        SetAttributes, 
+        SetBackgroundColor, 
+        SetForegroundColor, 
     }, 
-    execute, 
-    style::Print, 
     terminal::{
         self, 
-        disable_raw_mode, 
-        enable_raw_mode, 
+        BeginSynchronizedUpdate, 
         Clear, 
         ClearType, 
+        EndSynchronizedUpdate, 
         EnterAlternateScreen, 
-        LeaveAlternateScreen
+        LeaveAlternateScreen, 
     }, 
-    ExecutableCommand,
 };
 
 use crate::{
     backends::traits::DrawerTrair, layout::{
         Container, 
-        StyleLink
+        Contaiment, 
+        Dirty, 
+        StyleIndex
     }, style::{
-        Align, ContentWrap, Orientation, Size, Style 
-    }
+        Align, Color, ContentWrap, ControlChars, Escapes, Orientation, Sides, Size, Style, TextOverflow 
+    }, 
+    ansi, 
+    buffer::{Buffer, Cell}, 
+    span::{Span, SpanStyle}, 
+    text,
+    theme::Theme,
 };
 
-use super::colors::remove_colors_from_len;
+use super::colors::{to_crossterm_attributes, to_crossterm_color};
 
-pub struct Drawer {
+const MARQUEE_STEP_MS: u128 = 200;
+
+/// Region of the terminal the frames are drawn into.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Viewport {
+    /// The whole alternate screen, the previous screen is restored on close.
+    Fullscreen,
+    /// Rows below the cursor on the main screen, the last frame is left in the scrollback.
+    Inline(InlineHeight),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum InlineHeight {
+    Fixed(u16),
+    /// As many rows as the painted content uses, up to the terminal height.
+    Content,
+}
+
+/// Draws into any `io::Write` sink, the terminal on stdout by default.
+pub struct Drawer<W: Write = Stdout> {
+    writer: W,
+    /// Fixed frame size, when `None` the size of the terminal is used.
+    size: Option<(u16, u16)>,
+    viewport: Viewport,
+    /// Terminal row of the top of an inline viewport.
+    origin: u16,
+    /// Row to start an inline viewport at instead of the cursor row.
+    inline_origin: Option<u16>,
+    /// Rows below `origin` already claimed by an inline viewport.
+    reserved: u16,
+    terminal_height: u16,
+    /// Parts of every container in paint order (pre-order of the container tree).
     render_parts: Vec<RenderPart>,
+    buffer: Buffer,
+    previous: Buffer,
+    /// Regions that have to be repainted on the next `display`.
+    damage: Vec<(u32, u32, u32, u32)>,
+    repaint_all: bool,
+    full_redraw: bool,
+    synchronized_update: bool,
+    mouse_capture: bool,
+    keyboard_enhancement: bool,
+    /// Marquee step of the last displayed frame.
+    displayed_tick: u64,
 }
 
-impl Drawer {
+impl Drawer<Stdout> {
     pub fn new() -> Self {
+        Drawer::with_writer(stdout())
+    }
+}
+
+impl Default for Drawer<Stdout> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Write> Drawer<W> {
+    pub fn with_writer(writer: W) -> Self {
         Drawer {
+            writer,
+            size: None,
+            viewport: Viewport::Fullscreen,
+            origin: 0,
+            inline_origin: None,
+            reserved: 0,
+            terminal_height: 0,
             render_parts: vec![],
+            buffer: Buffer::default(),
+            previous: Buffer::default(),
+            damage: vec![],
+            repaint_all: true,
+            full_redraw: true,
+            synchronized_update: true,
+            mouse_capture: false,
+            keyboard_enhancement: false,
+            displayed_tick: 0,
         }
     }
+
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn writer_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Uses a fixed frame size instead of querying the terminal, needed when the
+    /// writer is not the controlling terminal (a file, a buffer or a custom pty).
+    pub fn set_size(&mut self, size: Option<(u16, u16)>) {
+        self.size = size;
+    }
+
+    /// Has to be set before `init`.
+    ///
+    /// An inline viewport starts at the cursor row, which `init` queries with a request
+    /// written to stdout and answered on stdin whatever the writer is. It has to run
+    /// before anything else reads terminal input. Use `set_inline_origin` when the
+    /// writer is not the terminal on stdout.
+    pub fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = viewport;
+    }
+
+    /// Terminal row an inline viewport starts at, `None` queries the cursor position.
+    pub fn set_inline_origin(&mut self, row: Option<u16>) {
+        self.inline_origin = row;
+    }
+
+    pub fn viewport(&self) -> &Viewport {
+        &self.viewport
+    }
+
+    /// Wraps every frame in the terminal synchronized update mode (DEC 2026), so the
+    /// terminal presents it at once. Enabled by default, terminals without support ignore it.
+    pub fn set_synchronized_update(&mut self, enabled: bool) {
+        self.synchronized_update = enabled;
+    }
+
+    /// Rows of the current frame shown in an inline viewport.
+    fn frame_height(&self) -> u16 {
+        let height = match self.viewport {
+            Viewport::Inline(InlineHeight::Content) => (0..self.buffer.height())
+                .rev()
+                .find(|y| (0..self.buffer.width()).any(|x| self.buffer.get(x, *y) != Some(&Cell::default())))
+                .map(|y| y + 1)
+                .unwrap_or(0),
+            _ => self.buffer.height(),
+        };
+        height.min(self.terminal_height as u32) as u16
+    }
+
+    /// Claims rows for an inline viewport, scrolling the screen up when they run past
+    /// its bottom. Content already drawn scrolls along with `origin`.
+    fn reserve(&mut self, output: &mut impl Write, height: u16) {
+        if height <= self.reserved {
+            return;
+        }
+        let _ = queue!(output, MoveTo(0, self.origin));
+        for _ in 1..height {
+            let _ = queue!(output, Print("\n"));
+        }
+        let scrolled = (self.origin + height).saturating_sub(self.terminal_height);
+        self.origin -= scrolled.min(self.origin);
+        self.reserved = height;
+    }
+
+    /// Terminal row the layout starts at.
+    fn screen_origin(&self) -> u16 {
+        match self.viewport {
+            Viewport::Fullscreen => 0,
+            Viewport::Inline(_) => self.origin,
+        }
+    }
+
+    fn find_part(&self, id: &str) -> Option<usize> {
+        self.render_parts.iter().position(|part| part.id == id)
+    }
+
+    /// Lays out the subtree of `id` again inside the geometry it had before.
+    fn relayout(&mut self, main_container: &Container, styles: &StyleIndex, id: &str) {
+        let (Some(index), Some(container)) = (self.find_part(id), main_container.find(id)) else {
+            return;
+        };
+        let allotted = self.render_parts[index].allotted;
+        let end = index + 1 + self.render_parts[index].descendants;
+        let parts = prepare_render_parts(container, styles, allotted);
+        let added = parts.len();
+
+        // ancestors are the only earlier parts whose subtree range reaches `index`
+        for (i, part) in self.render_parts[..index].iter_mut().enumerate() {
+            if i + part.descendants >= index {
+                part.descendants = part.descendants + added - (end - index);
+            }
+        }
+
+        self.render_parts.splice(index..end, parts);
+        self.damage.push(allotted);
+    }
 }
 
-impl DrawerTrair for Drawer {
-    fn render(&mut self, main_container: Container, style_links: Vec<StyleLink>) {
-        let geometry = terminal::size().unwrap();
+impl<W: Write> DrawerTrair for Drawer<W> {
+    fn set_mouse_capture(&mut self, enabled: bool) {
+        self.mouse_capture = enabled;
+    }
 
-        self.render_parts = prepare_render_parts(
-            main_container, 
-            style_links, 
-            (
-                geometry.0 as u32, 
-                geometry.1 as u32,
-                0,
-                0
-            ),
-        );
+    fn set_keyboard_enhancement(&mut self, enabled: bool) {
+        self.keyboard_enhancement = enabled;
+    }
+
+    fn init(&mut self) -> io::Result<()> {
+        match self.viewport {
+            Viewport::Fullscreen => {
+                queue!(self.writer, EnterAlternateScreen, Hide)?;
+            },
+            Viewport::Inline(_) => {
+                self.reserved = 0;
+                self.origin = match self.inline_origin {
+                    Some(origin) => origin,
+                    // a fixed size means the writer is not the terminal, the frame starts at its top
+                    None if self.size.is_some() => 0,
+                    None => {
+                        let (column, row) = term_cursor::position()?;
+                        if column > 0 {
+                            // start below the unfinished line, the terminal scrolls at its bottom
+                            queue!(self.writer, Print("\r\n"))?;
+                            (row + 1).min(terminal::size()?.1.saturating_sub(1))
+                        } else {
+                            row
+                        }
+                    },
+                };
+                queue!(self.writer, Hide)?;
+            },
+        }
+        if self.mouse_capture {
+            queue!(self.writer, EnableMouseCapture)?;
+        }
+        if self.keyboard_enhancement {
+            queue!(self.writer, PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES))?;
+        }
+        self.writer.flush()?;
+        self.invalidate();
+        Ok(())
+    }
 
-        let mut stdout = stdout();
-        let _ = stdout.execute(Clear(ClearType::All));
+    fn close(&mut self) {
+        if self.keyboard_enhancement {
+            let _ = queue!(self.writer, PopKeyboardEnhancementFlags);
+        }
+        if self.mouse_capture {
+            let _ = queue!(self.writer, DisableMouseCapture);
+        }
+        match self.viewport {
+            Viewport::Fullscreen => {
+                let _ = queue!(self.writer, SetAttribute(Attribute::Reset), LeaveAlternateScreen, Show);
+            },
+            Viewport::Inline(_) => {
+                // leave the cursor on the line below the last frame
+                let _ = queue!(self.writer, SetAttribute(Attribute::Reset));
+                if self.reserved > 0 {
+                    let _ = queue!(self.writer, MoveTo(0, self.origin + self.reserved - 1), Print("\r\n"));
+                } else {
+                    let _ = queue!(self.writer, MoveTo(0, self.origin));
+                }
+                let _ = queue!(self.writer, Show);
+                self.reserved = 0;
+            },
+        }
+        let _ = self.writer.flush();
     }
 
-    fn display(& self) {
-        for part in &self.render_parts {
-            part.display();
+    fn render(&mut self, main_container: &Container, styles: &StyleIndex, dirty: &Dirty) {
+        let terminal_size = match self.size {
+            Some(size) => size,
+            None => terminal::size().unwrap_or((80, 24)),
+        };
+        let geometry = match self.viewport {
+            Viewport::Inline(InlineHeight::Fixed(height)) => (terminal_size.0, height.min(terminal_size.1)),
+            _ => terminal_size,
+        };
+
+        if self.terminal_height != terminal_size.1 {
+            self.terminal_height = terminal_size.1;
+            // rows claimed before the resize may have been reflowed by the terminal
+            self.origin = self.origin.min(terminal_size.1.saturating_sub(1));
+            self.reserved = 0;
+        }
+
+        if self.buffer.width() != geometry.0 as u32 || self.buffer.height() != geometry.1 as u32 {
+            self.buffer = Buffer::new(geometry.0 as u32, geometry.1 as u32);
+            self.previous = Buffer::new(geometry.0 as u32, geometry.1 as u32);
+            self.full_redraw = true;
+            self.repaint_all = true;
+        }
+
+        if self.repaint_all || dirty.all || self.render_parts.is_empty() {
+            self.render_parts = prepare_render_parts(
+                main_container, 
+                styles, 
+                (
+                    geometry.0 as u32, 
+                    geometry.1 as u32,
+                    0,
+                    0
+                ),
+            );
+            self.repaint_all = true;
+            return;
+        }
+
+        for id in &dirty.layout {
+            self.relayout(main_container, styles, id);
+        }
+
+        for id in &dirty.contaiment {
+            let (Some(index), Some(container)) = (self.find_part(id), main_container.find(id)) else {
+                continue;
+            };
+            let part = &mut self.render_parts[index];
+            part.contaiment = resolve_contaiment(&container.contaiment, &styles.theme);
+            self.damage.push(part.geometry);
         }
     }
+
+    fn display(&mut self) {
+        self.displayed_tick = marquee_tick();
+        if self.repaint_all {
+            self.buffer.clear();
+            for part in &self.render_parts {
+                part.paint(&mut self.buffer);
+            }
+        } else {
+            let mut damage = std::mem::take(&mut self.damage);
+            damage.extend(self.render_parts
+                .iter()
+                .filter(|part| part.is_animated())
+                .map(|part| part.geometry));
+
+            for rect in damage {
+                self.buffer.set_clip(Some(rect));
+                self.buffer.fill(rect, &SpanStyle::default());
+                for part in self.render_parts.iter().filter(|part| intersects(part.geometry, rect)) {
+                    part.paint(&mut self.buffer);
+                }
+            }
+            self.buffer.set_clip(None);
+        }
+        self.repaint_all = false;
+        self.damage.clear();
+
+        let mut output: Vec<u8> = Vec::new();
+
+        if self.synchronized_update {
+            let _ = queue!(output, BeginSynchronizedUpdate);
+        }
+        let _ = queue!(output, Hide);
+
+        match self.viewport {
+            Viewport::Fullscreen => {
+                if self.full_redraw {
+                    let _ = queue!(output, Clear(ClearType::All));
+                    self.previous.clear();
+                }
+                queue_diff(&mut output, &self.buffer.diff(&self.previous), 0);
+            },
+            Viewport::Inline(_) => {
+                let height = self.frame_height();
+                self.reserve(&mut output, height);
+                if self.full_redraw {
+                    let _ = queue!(output, MoveTo(0, self.origin), Clear(ClearType::FromCursorDown));
+                    self.previous.clear();
+                }
+                let changes: Vec<(u32, u32, &Cell)> = self.buffer
+                    .diff(&self.previous)
+                    .into_iter()
+                    .filter(|(_, y, _)| *y < height as u32)
+                    .collect();
+                queue_diff(&mut output, &changes, self.origin);
+                if height < self.reserved {
+                    // content shrank, give the rows below back
+                    let _ = queue!(output, MoveTo(0, self.origin + height), Clear(ClearType::FromCursorDown));
+                    self.reserved = height;
+                }
+            },
+        }
+        self.full_redraw = false;
+        if self.synchronized_update {
+            let _ = queue!(output, EndSynchronizedUpdate);
+        }
+
+        // one write and one flush per frame
+        let _ = self.writer.write_all(&output);
+        let _ = self.writer.flush();
+
+        self.previous = self.buffer.clone();
+    }
+
+    fn needs_display(&self) -> bool {
+        let animated = self.render_parts.iter().any(RenderPart::is_animated);
+
+        self.repaint_all
+            || self.full_redraw
+            || !self.damage.is_empty()
+            || (animated && marquee_tick() != self.displayed_tick)
+    }
+
+    fn invalidate(&mut self) {
+        self.full_redraw = true;
+        self.repaint_all = true;
+    }
+
+    fn containers_at(&self, column: u16, row: u16) -> Vec<String> {
+        let Some(y) = row.checked_sub(self.screen_origin()) else {
+            return vec![];
+        };
+        let point = (1, 1, column as u32, y as u32);
+
+        // later parts are painted over earlier ones
+        self.render_parts
+            .iter()
+            .rev()
+            .filter(|part| intersects(part.geometry, point))
+            .map(|part| part.id.clone())
+            .collect()
+    }
+
+    fn rect_of(&self, id: &str) -> Option<(u32, u32, u32, u32)> {
+        let geometry = self.render_parts[self.find_part(id)?].geometry;
+        Some((geometry.0, geometry.1, geometry.2, geometry.3 + self.screen_origin() as u32))
+    }
+}
+
+/// Resolves theme variables used by span content.
+fn resolve_contaiment(contaiment: &Contaiment, theme: &Theme) -> Contaiment {
+    match contaiment {
+        Contaiment::Text(text) => Contaiment::Text(text.clone()),
+        Contaiment::Spans(spans) => Contaiment::Spans(spans
+            .iter()
+            .map(|span| Span::styled(&span.text, theme.apply_span_style(&span.style)))
+            .collect()),
+    }
+}
+
+fn intersects(a: (u32, u32, u32, u32), b: (u32, u32, u32, u32)) -> bool {
+    a.2 < b.2 + b.0 && b.2 < a.2 + a.0 && a.3 < b.3 + b.1 && b.3 < a.3 + a.1
 }
 
+/// Queues changed cells, moving the cursor and switching styles only when needed.
+/// Rows are shifted down by `origin`, the top row of the viewport on the terminal.
+fn queue_diff(output: &mut impl Write, changes: &[(u32, u32, &Cell)], origin: u16) {
+    let mut cursor: Option<(u32, u32)> = None;
+    let mut style: Option<&SpanStyle> = None;
+
+    for (x, y, cell) in changes {
+        if cell.is_continuation() {
+            continue;
+        }
+        if cursor != Some((*x, *y)) {
+            let _ = queue!(output, MoveTo(*x as u16, *y as u16 + origin));
+        }
+        if style != Some(&cell.style) {
+            queue_style(output, &cell.style);
+            style = Some(&cell.style);
+        }
+        let _ = queue!(output, Print(&cell.symbol));
+        cursor = Some((x + text::grapheme_width(&cell.symbol) as u32, *y));
+    }
+
+    let _ = queue!(output, SetAttribute(Attribute::Reset));
+}
+
+fn queue_style(output: &mut impl Write, style: &SpanStyle) {
+    let _ = queue!(
+        output,
+        SetAttribute(Attribute::Reset),
+        SetAttributes(to_crossterm_attributes(style)),
+        SetForegroundColor(
+            style.foreground.as_ref().map(to_crossterm_color).unwrap_or(crossterm::style::Color::Reset)
+        ),
+        SetBackgroundColor(
+            style.background.as_ref().map(to_crossterm_color).unwrap_or(crossterm::style::Color::Reset)
+        ),
+    );
+}
+
+
 struct RenderPart {
+    id: String,
+    /// Geometry given by the parent, before margins are applied.
+    allotted: (u32, u32, u32, u32),
+    /// Number of parts of the container subtree that directly follow this one.
+    descendants: usize,
     geometry: (u32, u32, u32, u32),
+    content_geometry: (u32, u32, u32, u32),
     wrap_contaiment: bool,
+    text_overflow: TextOverflow,
+    tab_width: u8,
+    control_chars: ControlChars,
+    escapes: Escapes,
     h_align: Align,
     v_align: Align,
-    contaiment: String,
+    foreground: Option<Color>,
+    background: Option<Color>,
+    contaiment: Contaiment,
 }
 
 impl RenderPart {
-    fn new(
-        geometry: (u32, u32, u32, u32),
-        wrap_contaiment: bool,
-        h_align: Align,
-        v_align: Align,
-        contaiment: String,
+    fn from_container(
+        allotted: (u32, u32, u32, u32), 
+        geometry: (u32, u32, u32, u32), 
+        content_geometry: (u32, u32, u32, u32), 
+        container: &Container, 
+        styles: &[Style],
+        theme: &Theme,
     ) -> Self {
-        RenderPart {
-            geometry,
-            wrap_contaiment,
-            h_align,
-            v_align,
-            contaiment,
-        }
-    }
-
-    fn from_container(geometry: (u32, u32, u32, u32), container: &Container, styles: &Vec<Style>) -> Self {
-        let mut margin = (0, 0, 0, 0);
         let mut wrap_contaiment = false;
+        let mut text_overflow = TextOverflow::Clip;
+        let mut tab_width = text::TAB_WIDTH;
+        let mut control_chars = ControlChars::Placeholder;
+        let mut escapes = Escapes::Allow;
         let mut h_align = Align::Left;
         let mut v_align = Align::Top;
-        let contaiment = container.contaiment.clone();
+        let mut foreground = None;
+        let mut background = None;
+        let contaiment = resolve_contaiment(&container.contaiment, theme);
 
         for style in styles {
             match style {
@@ -107,170 +566,178 @@ impl RenderPart {
                     ContentWrap::Wrap => wrap_contaiment = true,
                     ContentWrap::NoWrap => wrap_contaiment = false,
                 },
+                Style::TextOverflow(overflow) => text_overflow = overflow.clone(),
+                Style::TabWidth(width) => tab_width = *width,
+                Style::ControlChars(control) => control_chars = control.clone(),
+                Style::Escapes(escape) => escapes = escape.clone(),
                 Style::HAlign(align) => h_align = align.clone(),
                 Style::VAlign(align) => v_align = align.clone(),
+                Style::Foreground(color) => foreground = Some(color.clone()),
+                Style::Background(color) => background = Some(color.clone()),
                 _ => {},
             }
         }
 
         RenderPart {
+            id: container.id.clone(),
+            allotted,
+            descendants: 0,
             geometry,
+            content_geometry,
             wrap_contaiment,
+            text_overflow,
+            tab_width,
+            control_chars,
+            escapes,
             h_align,
             v_align,
+            foreground,
+            background,
             contaiment,
         }
     }
 
-    fn display(& self) {
-        let mut contaiment = self.contaiment.clone();
-        let mut contaiment_len = contaiment.len();
-        contaiment_len = remove_colors_from_len(contaiment_len, &contaiment);
-        if contaiment_len == 0 {return;}
+    fn is_animated(& self) -> bool {
+        matches!(self.text_overflow, TextOverflow::Marquee) && !self.wrap_contaiment
+    }
 
-        let mut h_pos = 0;
-        let mut v_pos = 0;
+    fn paint(& self, buffer: &mut Buffer) {
+        if let Some(background) = &self.background {
+            buffer.fill(self.geometry, &SpanStyle::new().bg(background.clone()));
+        }
 
-        h_pos = match self.h_align {
-            Align::Left => 0,
-            Align::Right => self.geometry.0 - contaiment_len as u32,
-            Align::Center => (self.geometry.0 - contaiment_len as u32) / 2,
-            _ => h_pos,
-        };
+        self.paint_contaiment(buffer);
+    }
 
-        v_pos = match self.v_align {
-            Align::Top => 0,
-            Align::Center => self.geometry.1 / 2,
-            Align::Bottom => self.geometry.1 - 1,
-            _ => v_pos,
+    fn paint_contaiment(& self, buffer: &mut Buffer) {
+        let base = SpanStyle {
+            foreground: self.foreground.clone(),
+            background: self.background.clone(),
+            ..SpanStyle::default()
         };
+        let contaiment = match &self.contaiment {
+            Contaiment::Text(text) => vec![Span::styled(text, base)],
+            Contaiment::Spans(spans) => spans
+                .iter()
+                .map(|span| Span::styled(&span.text, span.style.over(&base)))
+                .collect(),
+        };
+        let spans: Vec<Span> = contaiment
+            .iter()
+            .flat_map(|span| match self.escapes {
+                Escapes::Allow => ansi::parse_with(&span.text, &span.style),
+                Escapes::Strip => vec![Span::styled(&ansi::strip(&span.text), span.style.clone())],
+            })
+            .collect();
+        if text::spans_width(&spans) == 0 {return;}
 
-        let mut stdout = stdout();
-
-        let _ = stdout.execute(Hide);
+        let width = self.content_geometry.0 as usize;
+        let mut lines: Vec<(Vec<Span>, bool)> = Vec::new();
 
-        if self.wrap_contaiment {
-            for char in contaiment.chars() {
-                let _ = stdout.execute(MoveTo(h_pos as u16 + self.geometry.2 as u16, v_pos as u16 + self.geometry.3 as u16));
-                let _ = stdout.execute(Print(char));
-                h_pos += 1;
-                if h_pos == self.geometry.0 {
-                    h_pos = 0;
-                    v_pos += 1;
-                }
-                if v_pos > self.geometry.1 {
-                    break;
+        for paragraph in text::lines(&spans) {
+            let paragraph = text::sanitize(&paragraph, self.tab_width, &self.control_chars);
+            if self.wrap_contaiment {
+                let wrapped = text::wrap(&paragraph, width);
+                if wrapped.is_empty() {
+                    lines.push((vec![], false));
                 }
+                let last = wrapped.len().saturating_sub(1);
+                lines.extend(wrapped.into_iter().enumerate().map(|(i, line)| (line, i < last)));
+            } else {
+                let line = text::overflow(&paragraph, width, &self.text_overflow, marquee_tick());
+                lines.push((line, false));
             }
-        } else {
-            let _ = stdout.execute(MoveTo(h_pos as u16 + self.geometry.2 as u16, v_pos as u16 + self.geometry.3 as u16));
-            let size: usize = (self.geometry.0 - h_pos) as usize;
-            let _ = stdout.execute(Print(
-                &contaiment[0..if size > contaiment_len {contaiment_len} else {size}]
-            ));
         }
 
+        let block_height = lines.len() as u32;
+        let v_pos = match self.v_align {
+            Align::Center => self.content_geometry.1.saturating_sub(block_height) / 2,
+            Align::Bottom => self.content_geometry.1.saturating_sub(block_height),
+            _ => 0,
+        };
+
+        for (v_pos, (line, justify)) in (v_pos..self.content_geometry.1).zip(lines) {
+            let line_len = text::spans_width(&line) as u32;
+            let (line, h_pos) = match self.h_align {
+                Align::Right => (line, self.content_geometry.0.saturating_sub(line_len)),
+                Align::Center => (line, self.content_geometry.0.saturating_sub(line_len) / 2),
+                Align::Justify if justify => (text::justify(&line, width), 0),
+                _ => (line, 0),
+            };
+            buffer.put_spans(
+                h_pos + self.content_geometry.2, 
+                v_pos + self.content_geometry.3, 
+                &line, 
+                self.content_geometry.2 + self.content_geometry.0,
+            );
+        }
     }
 }
 
+fn marquee_tick() -> u64 {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    (millis / MARQUEE_STEP_MS) as u64
+}
+
 fn prepare_render_parts(
-    container: Container, 
-    style_links: Vec<StyleLink>,
+    container: &Container, 
+    style_index: &StyleIndex,
     mut geometry: (u32, u32, u32, u32),
 ) -> Vec<RenderPart> {
     let mut result: Vec<RenderPart> = Vec::new();
+    let allotted = geometry;
 
-    let styles = find_container_style(&style_links, &container);
+    let styles = style_index.styles_for(container);
 
     let mut horizontal = true;
 
-    for style in &styles {match style {
-        Style::Orientation(orientation) => match orientation {
+    for style in &styles {if let Style::Orientation(orientation) = style {
+        match orientation {
             Orientation::Horizontal => horizontal=true,
             Orientation::Vertical => horizontal=false,
-        },
-        _ => {},
+        }
     }}
 
-    for style in &styles {match style {
-        Style::Margin(size, size1, size2, size3) => {
-            let margin_left = match size {
-                Size::Percent(s) => find_real_size(*s, geometry.0),
-                Size::Fixed(s) => if *s > geometry.0 {geometry.0 - 1} else {*s},
-            };
-            let margin_top = match size1 {
-                Size::Percent(s) => find_real_size(*s, geometry.1),
-                Size::Fixed(s) => if *s > geometry.1 {geometry.1 - 1} else {*s},
-            };
-            let margin_right = match size2 {
-                Size::Percent(s) => find_real_size(*s, geometry.0),
-                Size::Fixed(s) => if *s > geometry.0 {geometry.0 - 1} else {*s},
-            };
-            let margin_bottom = match size3 {
-                Size::Percent(s) => find_real_size(*s, geometry.1),
-                Size::Fixed(s) => if *s > geometry.1 {geometry.1 - 1} else {*s},
-            };
-
-
-            geometry.0 -= margin_left;
-            geometry.2 += margin_left;
+    geometry = apply_sides(geometry, &Sides::margin(&styles));
+    let content_geometry = apply_sides(geometry, &Sides::padding(&styles));
 
-            geometry.1 -= margin_top;
-            geometry.3 += margin_top;
-
-            geometry.0 = if geometry.0 - margin_right > 1 {geometry.0 - margin_right} else {1};
-            geometry.1 = if geometry.1 - margin_bottom > 1 {geometry.1 - margin_bottom} else {1};
-            
-            break;
-        },
-        _ => {},
-    }}
-
-    result.push(RenderPart::from_container(geometry, &container, &styles));
+    result.push(RenderPart::from_container(allotted, geometry, content_geometry, container, &styles, &style_index.theme));
 
+    let geometry = content_geometry;
     let childs = container.childs();
     let child_sizes = find_childs_size(
-        &style_links, 
-        &childs, 
+        style_index, 
+        childs, 
         if horizontal {geometry.0} else {geometry.1},
     );
 
+    let mut offset = if horizontal {geometry.2} else {geometry.3};
+
     for (i, c) in childs.iter().enumerate() {
         result.extend(prepare_render_parts(
-            c.clone(), 
-            style_links.clone(), 
+            c, 
+            style_index, 
             (
                 if horizontal {child_sizes[i]} else {geometry.0},
                 if !horizontal {child_sizes[i]} else {geometry.1},
-                if !horizontal {geometry.2} else {
-                    let mut sum = geometry.2;
-                    child_sizes.iter().enumerate().for_each(|(j, f)| {
-                        if j < i {
-                            sum += f 
-                        }
-                    });
-                    sum
-                },
-                if horizontal {geometry.3} else {
-                    let mut sum = geometry.3;
-                    child_sizes.iter().enumerate().for_each(|(j, f)| {
-                        if j < i {
-                            sum += f
-                        }
-                    });
-                    sum
-                },
+                if horizontal {offset} else {geometry.2},
+                if !horizontal {offset} else {geometry.3},
             )
         ));
+        offset += child_sizes[i];
     }
 
+    result[0].descendants = result.len() - 1;
     result
 }
 
-fn find_childs_size(style_links: &Vec<StyleLink>, childs: &Vec<Container>, parent_size: u32) -> Vec<u32> {
+fn find_childs_size(style_index: &StyleIndex, childs: &[Container], parent_size: u32) -> Vec<u32> {
     let sizes: Vec<u32> = childs.iter()
         .map(|c| {
-            let styles = find_container_style(style_links, c);
+            let styles = style_index.styles_for(c);
             let mut min_size = Size::Fixed(1);
             let mut max_size = Size::Percent(100);
 
@@ -313,20 +780,34 @@ fn find_childs_size(style_links: &Vec<StyleLink>, childs: &Vec<Container>, paren
         .collect()
 }
 
-fn find_real_size(percent_size: u8, max_size: u32) -> u32 {
-    let percent_size = percent_size.clamp(1, 100);
-    ((percent_size as u32 * max_size) / 100).clamp(1, u32::max_value())
+fn apply_sides(mut geometry: (u32, u32, u32, u32), sides: &Sides) -> (u32, u32, u32, u32) {
+    let left = find_side_size(&sides.left, geometry.0);
+    let top = find_side_size(&sides.top, geometry.1);
+    let right = find_side_size(&sides.right, geometry.0);
+    let bottom = find_side_size(&sides.bottom, geometry.1);
+
+    geometry.0 = geometry.0.saturating_sub(left);
+    geometry.2 += left;
+
+    geometry.1 = geometry.1.saturating_sub(top);
+    geometry.3 += top;
+
+    geometry.0 = if geometry.0.saturating_sub(right) > 1 {geometry.0 - right} else {1};
+    geometry.1 = if geometry.1.saturating_sub(bottom) > 1 {geometry.1 - bottom} else {1};
+
+    geometry
 }
 
-fn find_container_style(style_links: &Vec<StyleLink>, container: &Container) -> Vec<Style> {
-    style_links
-        .into_iter()
-        .filter(|l| {
-            l.id.as_ref().map_or(false, |id| id.to_string() == container.id)
-                || l.class.as_ref().map_or(false, |class| container.classes.contains(class))
-        })
-        .flat_map(|l| l.style.clone())
-        .collect()
+fn find_side_size(size: &Size, max_size: u32) -> u32 {
+    match size {
+        Size::Percent(s) => find_real_size(*s, max_size),
+        Size::Fixed(s) => if *s >= max_size {max_size.saturating_sub(1)} else {*s},
+    }
+}
+
+fn find_real_size(percent_size: u8, max_size: u32) -> u32 {
+    let percent_size = percent_size.clamp(1, 100);
+    ((percent_size as u32 * max_size) / 100).clamp(1, u32::MAX)
 }
 
 //fn collect_render_parts(container: Container, style_links: Vec<StyleLink>, w: u32, h: u32, x: u32, y: u32, horizontal: bool) -> Vec<RenderPart> {
@@ -420,4 +901,186 @@ fn find_container_style(style_links: &Vec<StyleLink>, container: &Container) ->
 //
 //
 //
-//
\ No newline at end of file
+//
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::layout::StyleLink;
+
+    fn drawer() -> Drawer<Vec<u8>> {
+        let mut drawer = Drawer::with_writer(Vec::new());
+        drawer.set_size(Some((24, 12)));
+        drawer
+    }
+
+    fn styles() -> StyleIndex {
+        let links = [
+            StyleLink {id: Some("b".to_string()), class: None, style: vec![Style::Orientation(Orientation::Vertical)]},
+            StyleLink {id: None, class: Some("pad".to_string()), style: vec![Style::Padding(
+                Size::Fixed(1), Size::Fixed(0), Size::Fixed(1), Size::Fixed(0),
+            )]},
+        ];
+        StyleIndex::new(&links, Theme::default())
+    }
+
+    fn leaf(id: &str) -> Container {
+        Container::new(id, id, vec![], vec![])
+    }
+
+    fn tree() -> Container {
+        Container::new("main", "", vec![], vec![
+            Container::new("a", "", vec!["pad"], vec![leaf("a1"), leaf("a2")]),
+            Container::new("b", "", vec![], vec![
+                Container::new("b1", "", vec!["pad"], vec![leaf("b11")]),
+            ]),
+        ])
+    }
+
+    fn all() -> Dirty {
+        Dirty {all: true, ..Dirty::default()}
+    }
+
+    fn layout(ids: &[&str]) -> Dirty {
+        Dirty {layout: ids.iter().map(|id| id.to_string()).collect::<HashSet<_>>(), ..Dirty::default()}
+    }
+
+    /// Renders the first frame, so later renders take the incremental path.
+    fn first_frame(main_container: &Container, styles: &StyleIndex) -> Drawer<Vec<u8>> {
+        let mut drawer = drawer();
+        drawer.render(main_container, styles, &all());
+        drawer.display();
+        drawer
+    }
+
+    /// Checks the parts of an incrementally updated drawer against a fresh full layout.
+    fn assert_matches_full_layout(incremental: &mut Drawer<Vec<u8>>, main_container: &Container, styles: &StyleIndex) {
+        let mut full = drawer();
+        full.render(main_container, styles, &all());
+
+        let parts = |drawer: &Drawer<Vec<u8>>| -> Vec<_> {
+            drawer.render_parts
+                .iter()
+                .map(|part| (part.id.clone(), part.allotted, part.geometry, part.descendants))
+                .collect()
+        };
+        assert_eq!(parts(incremental), parts(&full));
+
+        for part in &full.render_parts {
+            assert_eq!(incremental.rect_of(&part.id), full.rect_of(&part.id), "rect of {}", part.id);
+        }
+        for row in 0..12 {
+            for column in 0..24 {
+                assert_eq!(incremental.containers_at(column, row), full.containers_at(column, row));
+            }
+        }
+
+        incremental.display();
+        full.display();
+        assert!(incremental.buffer.diff(&full.buffer).is_empty());
+    }
+
+    #[test]
+    fn relayout_after_removing_a_child() {
+        let styles = styles();
+        let mut main_container = tree();
+        let mut drawer = first_frame(&main_container, &styles);
+
+        if let Some(a) = main_container.find_mut("a") {
+            a.remove_child("a2");
+        }
+        drawer.render(&main_container, &styles, &layout(&["a"]));
+
+        assert!(!drawer.repaint_all);
+        assert_matches_full_layout(&mut drawer, &main_container, &styles);
+    }
+
+    #[test]
+    fn relayout_after_attaching_nested_children() {
+        let styles = styles();
+        let mut main_container = tree();
+        let mut drawer = first_frame(&main_container, &styles);
+
+        if let Some(b1) = main_container.find_mut("b1") {
+            b1.add_child(Container::new("c", "", vec!["pad"], vec![leaf("c1"), leaf("c2")]));
+        }
+        drawer.render(&main_container, &styles, &layout(&["b1"]));
+
+        assert!(!drawer.repaint_all);
+        assert_matches_full_layout(&mut drawer, &main_container, &styles);
+    }
+
+    #[test]
+    fn relayout_parent_and_child_in_one_render() {
+        let styles = styles();
+        let mut main_container = tree();
+        let mut drawer = first_frame(&main_container, &styles);
+
+        if let Some(b) = main_container.find_mut("b") {
+            b.add_child(Container::new("b2", "", vec![], vec![leaf("b21")]));
+        }
+        if let Some(b1) = main_container.find_mut("b1") {
+            b1.remove_child("b11");
+            b1.add_child(leaf("b12"));
+            b1.add_child(leaf("b13"));
+        }
+        drawer.render(&main_container, &styles, &layout(&["b", "b1"]));
+
+        assert!(!drawer.repaint_all);
+        assert_matches_full_layout(&mut drawer, &main_container, &styles);
+    }
+
+    #[test]
+    fn relayout_in_sequence() {
+        let styles = styles();
+        let mut main_container = tree();
+        let mut drawer = first_frame(&main_container, &styles);
+
+        if let Some(a) = main_container.find_mut("a") {
+            a.add_child(Container::new("a3", "", vec![], vec![leaf("a31"), leaf("a32")]));
+        }
+        drawer.render(&main_container, &styles, &layout(&["a"]));
+        drawer.display();
+        if let Some(a3) = main_container.find_mut("a3") {
+            a3.remove_child("a31");
+        }
+        if let Some(a) = main_container.find_mut("a") {
+            a.remove_child("a1");
+        }
+        drawer.render(&main_container, &styles, &layout(&["a3", "a"]));
+
+        assert!(!drawer.repaint_all);
+        assert_matches_full_layout(&mut drawer, &main_container, &styles);
+    }
+
+    #[test]
+    fn frame_is_written_to_the_writer() {
+        let main_container = Container::new("main", "hello", vec![], vec![]);
+        let mut drawer = drawer();
+        drawer.render(&main_container, &StyleIndex::default(), &all());
+        drawer.display();
+
+        let output = String::from_utf8_lossy(drawer.writer()).to_string();
+        let mut clear = Vec::new();
+        let _ = queue!(clear, Clear(ClearType::All));
+        assert!(output.contains(String::from_utf8_lossy(&clear).as_ref()));
+        assert!(output.contains("hello"));
+    }
+
+    #[test]
+    fn unchanged_frame_writes_only_the_wrapper() {
+        let main_container = Container::new("main", "hello", vec![], vec![]);
+        let mut drawer = drawer();
+        drawer.render(&main_container, &StyleIndex::default(), &all());
+        drawer.display();
+        drawer.writer_mut().clear();
+
+        assert!(!drawer.needs_display());
+        drawer.display();
+
+        let mut wrapper = Vec::new();
+        let _ = queue!(wrapper, BeginSynchronizedUpdate, Hide, SetAttribute(Attribute::Reset), EndSynchronizedUpdate);
+        assert_eq!(*drawer.writer(), wrapper);
+    }
+}
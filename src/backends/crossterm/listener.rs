@@ -1,66 +1,154 @@
 use std::{collections::HashMap, sync::mpsc::{channel, Receiver, Sender}, thread};
 
-use crate::backends::{backend::{Input, InputAction, InputListenerTrait, Key, KeyCode}, traits::ListenerTrait};
+use crate::{
+    backends::{
+        backend::{
+            Event, 
+            HandlerId, 
+            Input, 
+            InputAction, 
+            Key, 
+            KeyCode, 
+            Modifiers, 
+            MouseAction, 
+            MouseButton, 
+            MouseEvent, 
+        }, 
+        traits::ListenerTrait,
+    }, 
+    keymap::{Keymap, Modes},
+};
+
+type Callbacks = HashMap<Key, Vec<(HandlerId, Box<dyn FnMut()>)>>;
 
 pub struct Listener {
-    press_callbacks: HashMap<KeyCode, Box<dyn FnMut()>>,
-    hold_callbacks: HashMap<KeyCode, Box<dyn FnMut()>>,
-    release_callbacks: HashMap<KeyCode, Box<dyn FnMut()>>,
-    event_sender: Sender<Input>,
+    press_callbacks: Callbacks,
+    hold_callbacks: Callbacks,
+    release_callbacks: Callbacks,
+    next_handler_id: u64,
+    /// Mode layers, matched on press before `keymap`.
+    pub modes: Modes,
+    /// Key sequence bindings, matched on press before the single key callbacks.
+    pub keymap: Keymap,
+    event_sender: Sender<Event>,
 }
 
 impl Listener {
-    pub fn new() -> (Self, Receiver<Input>) {
+    pub fn new() -> (Self, Receiver<Event>) {
         let (tx, rx) = channel();
 
         let listener = Listener {
             press_callbacks: HashMap::new(),
             hold_callbacks: HashMap::new(),
             release_callbacks: HashMap::new(),
+            next_handler_id: 0,
+            modes: Modes::new(),
+            keymap: Keymap::new(),
             event_sender: tx,
         };
 
         (listener, rx)
     }
 
-    pub fn on_press<F>(&mut self, key_code: KeyCode, func: F)
+    /// Binds a plain `KeyCode` or a `Key` with modifiers, e.g. `Key::new(KeyCode::Char('s')).ctrl()`.
+    /// Callbacks of the same key run in registration order.
+    pub fn on_press<F>(&mut self, key: impl Into<Key>, func: F) -> HandlerId
     where
         F: FnMut() + 'static,
     {
-        self.press_callbacks.insert(key_code, Box::new(func));
+        let id = self.next_id();
+        self.press_callbacks.entry(key.into()).or_default().push((id, Box::new(func)));
+        id
     }
 
-    pub fn on_hold<F>(&mut self, key_code: KeyCode, func: F)
+    /// Repeats and releases are only reported by terminals supporting keyboard enhancement.
+    pub fn on_hold<F>(&mut self, key: impl Into<Key>, func: F) -> HandlerId
     where
         F: FnMut() + 'static,
     {
-        self.hold_callbacks.insert(key_code, Box::new(func));
+        let id = self.next_id();
+        self.hold_callbacks.entry(key.into()).or_default().push((id, Box::new(func)));
+        id
     }
 
-    pub fn on_release<F>(&mut self, key_code: KeyCode, func: F)
+    pub fn on_release<F>(&mut self, key: impl Into<Key>, func: F) -> HandlerId
     where
         F: FnMut() + 'static,
     {
-        self.release_callbacks.insert(key_code, Box::new(func));
+        let id = self.next_id();
+        self.release_callbacks.entry(key.into()).or_default().push((id, Box::new(func)));
+        id
+    }
+
+    /// Keys of the sequence being typed, for which-key style hints.
+    pub fn pending_keys(&self) -> &[Key] {
+        match self.modes.pending() {
+            [] => self.keymap.pending(),
+            pending => pending,
+        }
+    }
+
+    /// Removes a callback registered with `on_press`, `on_hold` or `on_release`.
+    pub fn remove(&mut self, id: HandlerId) -> bool {
+        [&mut self.press_callbacks, &mut self.hold_callbacks, &mut self.release_callbacks]
+            .into_iter()
+            .flat_map(|callbacks| callbacks.values_mut())
+            .any(|callbacks| {
+                let len = callbacks.len();
+                callbacks.retain(|(handler_id, _)| *handler_id != id);
+                callbacks.len() != len
+            })
     }
 
     pub fn trigger(&mut self, input: Input) {
-        if let Some(callback) = self.press_callbacks.get_mut(&input.key_code) {
-            callback();
+        if let InputAction::Press = input.action {
+            let key = input.key();
+            // checked before feeding, a binding may switch to a layer that does not fall through
+            let falls_through = self.modes.falls_through();
+            if self.modes.feed(key) || (falls_through && self.keymap.feed(key)) {
+                return;
+            }
+        }
+        let callbacks = match input.action {
+            InputAction::Press => &mut self.press_callbacks,
+            InputAction::Hold => &mut self.hold_callbacks,
+            InputAction::Release => &mut self.release_callbacks,
+        };
+        if let Some(callbacks) = callbacks.get_mut(&input.key()) {
+            for (_, callback) in callbacks {
+                callback();
+            }
         }
     }
+
+    fn next_id(&mut self) -> HandlerId {
+        self.next_handler_id += 1;
+        HandlerId::new(self.next_handler_id)
+    }
 }
 
 impl ListenerTrait for Listener {
+    fn tick(&mut self) {
+        self.modes.tick();
+        self.keymap.tick();
+    }
+
+    fn mode(&self) -> Option<String> {
+        self.modes.current()
+    }
+
     fn watch_input(&self) {
         let tx = self.event_sender.clone();
         thread::spawn(move || {
             loop {
-                if let Ok(event) = crossterm::event::read() {
-                    if let crossterm::event::Event::Key(key_event) = event {
-                        let input = key_event_to_input(key_event);
-                        tx.send(input).unwrap();
-                    }
+                let event = match crossterm::event::read() {
+                    Ok(crossterm::event::Event::Key(key_event)) => Event::Input(key_event_to_input(key_event)),
+                    Ok(crossterm::event::Event::Mouse(mouse_event)) => Event::Mouse(to_mouse_event(mouse_event)),
+                    Ok(crossterm::event::Event::Resize(columns, rows)) => Event::Resize(columns, rows),
+                    _ => continue,
+                };
+                if tx.send(event).is_err() {
+                    break;
                 }
             }
         });
@@ -103,8 +191,52 @@ fn key_event_to_input(event: crossterm::event::KeyEvent) -> Input {
         crossterm::event::KeyEventKind::Release => {InputAction::Release},
     };
 
+    let mut modifiers = to_modifiers(event.modifiers);
+    if let KeyCode::Char(_) | KeyCode::BackTab = key_code {
+        modifiers.remove(Modifiers::SHIFT);
+    }
+
     Input {
         key_code,
+        modifiers,
         action,
     }
+}
+
+fn to_mouse_event(event: crossterm::event::MouseEvent) -> MouseEvent {
+    use crossterm::event::MouseEventKind;
+
+    let button = |button: crossterm::event::MouseButton| match button {
+        crossterm::event::MouseButton::Left => MouseButton::Left,
+        crossterm::event::MouseButton::Right => MouseButton::Right,
+        crossterm::event::MouseButton::Middle => MouseButton::Middle,
+    };
+    let action = match event.kind {
+        MouseEventKind::Down(b) => MouseAction::Down(button(b)),
+        MouseEventKind::Up(b) => MouseAction::Up(button(b)),
+        MouseEventKind::Drag(b) => MouseAction::Drag(button(b)),
+        MouseEventKind::Moved => MouseAction::Move,
+        MouseEventKind::ScrollUp => MouseAction::ScrollUp,
+        MouseEventKind::ScrollDown => MouseAction::ScrollDown,
+        MouseEventKind::ScrollLeft => MouseAction::ScrollLeft,
+        MouseEventKind::ScrollRight => MouseAction::ScrollRight,
+    };
+
+    MouseEvent::new(action, event.column, event.row, to_modifiers(event.modifiers))
+}
+
+fn to_modifiers(modifiers: crossterm::event::KeyModifiers) -> Modifiers {
+    use crossterm::event::KeyModifiers;
+
+    [
+        (KeyModifiers::SHIFT, Modifiers::SHIFT),
+        (KeyModifiers::CONTROL, Modifiers::CONTROL),
+        (KeyModifiers::ALT, Modifiers::ALT),
+        (KeyModifiers::SUPER, Modifiers::SUPER),
+        (KeyModifiers::HYPER, Modifiers::HYPER),
+        (KeyModifiers::META, Modifiers::META),
+    ]
+        .into_iter()
+        .filter(|(crossterm_modifier, _)| modifiers.contains(*crossterm_modifier))
+        .fold(Modifiers::NONE, |result, (_, modifier)| result | modifier)
 }
\ No newline at end of file
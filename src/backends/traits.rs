@@ -1,17 +1,43 @@
-use crate::layout::{Container, StyleLink};
+use std::io;
+
+use crate::layout::{Container, Dirty, StyleIndex};
 
 pub trait BackendTrait {
-    fn init(&mut self);
-    fn close(& self);
-    fn render(&mut self, main_container: Container, style_links: Vec<StyleLink>);
-    fn display(&self);
+    fn init(&mut self) -> io::Result<()>;
+    fn close(&mut self);
+    fn render(&mut self, main_container: &Container, styles: &StyleIndex, dirty: &Dirty);
+    fn display(&mut self);
+    /// Whether `display` would change anything on the terminal.
+    fn needs_display(&self) -> bool;
+    /// Forces the next `display` to clear the terminal and repaint every cell.
+    fn invalidate(&mut self);
+    /// Ids of the containers laid out over a terminal cell, topmost first.
+    fn containers_at(&self, column: u16, row: u16) -> Vec<String>;
+    /// `(width, height, x, y)` of a container on the terminal, margins excluded.
+    fn rect_of(&self, id: &str) -> Option<(u32, u32, u32, u32)>;
+    /// Called periodically by `Rll::run`, e.g. to time out pending key sequences.
+    fn tick(&mut self);
+    /// Name of the current input mode, if the listener has modes.
+    fn mode(&self) -> Option<String>;
 }
 
 pub trait DrawerTrair {
-    fn render(&mut self, main_container: Container, style_links: Vec<StyleLink>);
-    fn display(&self);
+    /// Whether `init` turns on mouse reporting, `close` turns it off again.
+    fn set_mouse_capture(&mut self, enabled: bool);
+    /// Whether `init` asks the terminal to report key repeats and releases, `close` stops it.
+    fn set_keyboard_enhancement(&mut self, enabled: bool);
+    fn init(&mut self) -> io::Result<()>;
+    fn close(&mut self);
+    fn render(&mut self, main_container: &Container, styles: &StyleIndex, dirty: &Dirty);
+    fn display(&mut self);
+    fn needs_display(&self) -> bool;
+    fn invalidate(&mut self);
+    fn containers_at(&self, column: u16, row: u16) -> Vec<String>;
+    fn rect_of(&self, id: &str) -> Option<(u32, u32, u32, u32)>;
 }
 
 pub trait ListenerTrait {
     fn watch_input(& self);
+    fn tick(&mut self);
+    fn mode(&self) -> Option<String>;
 }
\ No newline at end of file
@@ -0,0 +1,264 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    span::{Span, SpanStyle},
+    text,
+};
+
+/// A single terminal cell. The cell after a double-width grapheme keeps an
+/// empty symbol and is covered by the grapheme on the left.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cell {
+    pub symbol: String,
+    pub style: SpanStyle,
+}
+
+impl Cell {
+    pub fn is_continuation(&self) -> bool {
+        self.symbol.is_empty()
+    }
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            symbol: " ".to_string(),
+            style: SpanStyle::default(),
+        }
+    }
+}
+
+/// Grid of styled cells that containers are painted into before flushing.
+#[derive(Clone, Default)]
+pub struct Buffer {
+    width: u32,
+    height: u32,
+    cells: Vec<Cell>,
+    clip: Option<(u32, u32, u32, u32)>,
+}
+
+impl Buffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Buffer {
+            width,
+            height,
+            cells: vec![Cell::default(); (width * height) as usize],
+            clip: None,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Option<&Cell> {
+        if x < self.width && y < self.height {
+            self.cells.get((y * self.width + x) as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Restricts writes to the `(width, height, x, y)` rect, `None` removes the restriction.
+    pub fn set_clip(&mut self, clip: Option<(u32, u32, u32, u32)>) {
+        self.clip = clip;
+    }
+
+    /// Replaces a cell, blanking halves of double-width graphemes it would split.
+    pub fn set(&mut self, x: u32, y: u32, cell: Cell) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        if let Some(clip) = self.clip {
+            if x < clip.2 || x >= clip.2 + clip.0 || y < clip.3 || y >= clip.3 + clip.1 {
+                return;
+            }
+        }
+        let index = (y * self.width + x) as usize;
+
+        if !cell.is_continuation() && self.cells[index].is_continuation() && x > 0 {
+            self.cells[index - 1] = Cell::default();
+        }
+        if text::grapheme_width(&cell.symbol) < 2
+            && x + 1 < self.width
+            && self.cells[index + 1].is_continuation()
+        {
+            self.cells[index + 1] = Cell::default();
+        }
+
+        self.cells[index] = cell;
+    }
+
+    /// Resets every cell to a blank default cell.
+    pub fn clear(&mut self) {
+        self.cells.iter_mut().for_each(|cell| *cell = Cell::default());
+    }
+
+    /// Fills the `(width, height, x, y)` rect with blanks of the given style.
+    pub fn fill(&mut self, geometry: (u32, u32, u32, u32), style: &SpanStyle) {
+        for y in geometry.3..geometry.3 + geometry.1 {
+            for x in geometry.2..geometry.2 + geometry.0 {
+                self.set(x, y, Cell {
+                    symbol: " ".to_string(),
+                    style: style.clone(),
+                });
+            }
+        }
+    }
+
+    /// Writes spans starting at `(x, y)`, clipping graphemes that would cross `max_x`.
+    pub fn put_spans(&mut self, mut x: u32, y: u32, spans: &[Span], max_x: u32) {
+        let max_x = max_x.min(self.width);
+
+        for span in spans {
+            for grapheme in span.text.graphemes(true) {
+                let width = text::grapheme_width(grapheme) as u32;
+                if width == 0 {
+                    continue;
+                }
+                if x + width > max_x {
+                    return;
+                }
+                self.set(x, y, Cell {
+                    symbol: grapheme.to_string(),
+                    style: span.style.clone(),
+                });
+                for i in 1..width {
+                    self.set(x + i, y, Cell {
+                        symbol: String::new(),
+                        style: span.style.clone(),
+                    });
+                }
+                x += width;
+            }
+        }
+    }
+
+    /// Cells that differ from `previous`, in row-major order.
+    ///
+    /// Buffers of different size are compared as if `previous` was blank.
+    pub fn diff<'a>(&'a self, previous: &Buffer) -> Vec<(u32, u32, &'a Cell)> {
+        let same_size = self.width == previous.width && self.height == previous.height;
+        let blank = Cell::default();
+
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(i, cell)| {
+                let old = if same_size {&previous.cells[*i]} else {&blank};
+                *cell != old
+            })
+            .map(|(i, cell)| (i as u32 % self.width, i as u32 / self.width, cell))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(buffer: &Buffer, y: u32) -> String {
+        (0..buffer.width()).filter_map(|x| buffer.get(x, y)).map(|cell| cell.symbol.as_str()).collect()
+    }
+
+    #[test]
+    fn wide_graphemes_cover_two_cells() {
+        let mut buffer = Buffer::new(4, 1);
+        buffer.put_spans(0, 0, &[Span::raw("日a")], 4);
+
+        assert_eq!(buffer.get(0, 0).unwrap().symbol, "日");
+        assert!(buffer.get(1, 0).unwrap().is_continuation());
+        assert_eq!(row(&buffer, 0), "日a ");
+    }
+
+    #[test]
+    fn wide_grapheme_crossing_max_x_is_dropped() {
+        let mut buffer = Buffer::new(4, 1);
+        buffer.put_spans(0, 0, &[Span::raw("ab日")], 3);
+
+        assert_eq!(row(&buffer, 0), "ab  ");
+    }
+
+    #[test]
+    fn overwriting_half_of_a_wide_grapheme_blanks_the_other_half() {
+        let mut buffer = Buffer::new(4, 1);
+        buffer.put_spans(0, 0, &[Span::raw("日本")], 4);
+        buffer.put_spans(1, 0, &[Span::raw("x")], 4);
+
+        assert_eq!(row(&buffer, 0), " x本");
+
+        buffer.put_spans(2, 0, &[Span::raw("y")], 4);
+        assert_eq!(row(&buffer, 0), " xy ");
+    }
+
+    #[test]
+    fn clip_restricts_writes() {
+        let mut buffer = Buffer::new(4, 2);
+        buffer.set_clip(Some((2, 1, 1, 1)));
+        buffer.fill((4, 2, 0, 0), &SpanStyle::new().reverse());
+
+        let reversed: Vec<bool> = (0..4).map(|x| buffer.get(x, 1).unwrap().style.reverse).collect();
+        assert_eq!(reversed, vec![false, true, true, false]);
+        assert!(!buffer.get(1, 0).unwrap().style.reverse);
+
+        buffer.put_spans(0, 1, &[Span::raw("abcd")], 4);
+        assert_eq!(row(&buffer, 0), "    ");
+        assert_eq!(row(&buffer, 1), " bc ");
+
+        buffer.set_clip(None);
+        buffer.put_spans(0, 0, &[Span::raw("z")], 4);
+        assert_eq!(row(&buffer, 0), "z   ");
+    }
+
+    #[test]
+    fn diff_reports_changed_cells_in_row_major_order() {
+        let previous = Buffer::new(3, 2);
+        let mut buffer = previous.clone();
+        buffer.put_spans(2, 0, &[Span::raw("a")], 3);
+        buffer.put_spans(0, 1, &[Span::raw("b")], 3);
+
+        let changes: Vec<(u32, u32, &str)> = buffer
+            .diff(&previous)
+            .into_iter()
+            .map(|(x, y, cell)| (x, y, cell.symbol.as_str()))
+            .collect();
+        assert_eq!(changes, vec![(2, 0, "a"), (0, 1, "b")]);
+        assert!(buffer.diff(&buffer.clone()).is_empty());
+    }
+
+    #[test]
+    fn diff_includes_both_halves_of_a_changed_wide_cell() {
+        let previous = Buffer::new(3, 1);
+        let mut buffer = previous.clone();
+        buffer.put_spans(0, 0, &[Span::raw("日")], 3);
+
+        let changes: Vec<(u32, &str)> = buffer
+            .diff(&previous)
+            .into_iter()
+            .map(|(x, _, cell)| (x, cell.symbol.as_str()))
+            .collect();
+        assert_eq!(changes, vec![(0, "日"), (1, "")]);
+    }
+
+    #[test]
+    fn diff_style_only_change() {
+        let previous = Buffer::new(2, 1);
+        let mut buffer = previous.clone();
+        buffer.fill((1, 1, 1, 0), &SpanStyle::new().bold());
+
+        assert_eq!(buffer.diff(&previous).len(), 1);
+    }
+
+    #[test]
+    fn diff_against_a_different_size_compares_with_blank() {
+        let previous = Buffer::new(1, 1);
+        let mut buffer = Buffer::new(2, 1);
+        buffer.put_spans(1, 0, &[Span::raw("a")], 2);
+
+        assert_eq!(buffer.diff(&previous).len(), 1);
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+
+use crate::backends::backend::{Event, InputAction};
+
+/// Kinds of events container handlers can be registered for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    /// Key presses and repeats, targeted at the focused container.
+    Key,
+    /// Key releases, only reported with keyboard enhancement on.
+    KeyRelease,
+    /// Mouse input, targeted at the deepest container under the cursor.
+    Mouse,
+    Focus,
+    Blur,
+}
+
+impl EventKind {
+    pub fn of(event: &Event) -> Option<EventKind> {
+        match event {
+            Event::Input(input) => match input.action {
+                InputAction::Release => Some(EventKind::KeyRelease),
+                InputAction::Press | InputAction::Hold => Some(EventKind::Key),
+            },
+            Event::Mouse(_) => Some(EventKind::Mouse),
+            Event::Focus(_) => Some(EventKind::Focus),
+            Event::Blur(_) => Some(EventKind::Blur),
+            Event::Resize(..) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// From `main_container` down to the parent of the target.
+    Capture,
+    Target,
+    /// From the parent of the target up to `main_container`.
+    Bubble,
+}
+
+/// State of a single dispatch, passed to every handler along the path.
+pub struct EventContext {
+    /// Id of the container the event is targeted at.
+    pub target: String,
+    /// Id of the container whose handler is running.
+    pub current: String,
+    pub phase: Phase,
+    propagation_stopped: bool,
+    default_prevented: bool,
+}
+
+impl EventContext {
+    /// Skips the handlers of the remaining containers, the current one still runs all of its own.
+    pub fn stop_propagation(&mut self) {
+        self.propagation_stopped = true;
+    }
+
+    /// Skips the built-in handling of the event, e.g. Tab moving focus.
+    pub fn prevent_default(&mut self) {
+        self.default_prevented = true;
+    }
+
+    pub fn is_propagation_stopped(&self) -> bool {
+        self.propagation_stopped
+    }
+
+    pub fn is_default_prevented(&self) -> bool {
+        self.default_prevented
+    }
+}
+
+pub type Handler = Box<dyn FnMut(&Event, &mut EventContext)>;
+
+struct Registration {
+    kind: EventKind,
+    capture: bool,
+    handler: Handler,
+}
+
+/// Event handlers registered per container id.
+#[derive(Default)]
+pub struct Handlers {
+    by_id: HashMap<String, Vec<Registration>>,
+}
+
+impl Handlers {
+    pub fn add(&mut self, id: &str, kind: EventKind, capture: bool, handler: Handler) {
+        self.by_id.entry(id.to_string()).or_default().push(Registration {
+            kind,
+            capture,
+            handler,
+        });
+    }
+
+    pub fn remove(&mut self, id: &str) {
+        self.by_id.remove(id);
+    }
+
+    /// Runs capture handlers from the root down to the target, then bubble handlers back up.
+    ///
+    /// `path` holds the ids from `main_container` to the target.
+    pub fn dispatch(&mut self, path: &[String], event: &Event) -> EventContext {
+        let mut context = EventContext {
+            target: path.last().cloned().unwrap_or_default(),
+            current: String::new(),
+            phase: Phase::Capture,
+            propagation_stopped: false,
+            default_prevented: false,
+        };
+        let Some(kind) = EventKind::of(event) else {
+            return context;
+        };
+        let last = path.len().saturating_sub(1);
+
+        let capture = path.iter().enumerate().map(|(i, id)| (id, true, i == last));
+        let bubble = path.iter().enumerate().rev().map(|(i, id)| (id, false, i == last));
+
+        for (id, capture, at_target) in capture.chain(bubble) {
+            if context.propagation_stopped {
+                break;
+            }
+            let Some(registrations) = self.by_id.get_mut(id) else {
+                continue;
+            };
+            context.current = id.clone();
+            context.phase = match (at_target, capture) {
+                (true, _) => Phase::Target,
+                (false, true) => Phase::Capture,
+                (false, false) => Phase::Bubble,
+            };
+            for registration in registrations
+                .iter_mut()
+                .filter(|registration| registration.kind == kind && registration.capture == capture)
+            {
+                (registration.handler)(event, &mut context);
+            }
+        }
+
+        context
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::{
+        backends::{
+            backend::{Input, KeyCode},
+            crossterm::{backend::Backend, drawer::Drawer, listener::Listener},
+        },
+        layout::{Container, Rll},
+        style::Style,
+    };
+
+    fn key(action: InputAction) -> Event {
+        Event::Input(Input::new(KeyCode::Char('a'), action))
+    }
+
+    fn path(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    type Log = Rc<RefCell<Vec<(String, Phase, &'static str)>>>;
+
+    /// Adds a Key handler logging the container, phase and `name`.
+    fn log(handlers: &mut Handlers, log: &Log, id: &str, capture: bool, name: &'static str) {
+        let log = log.clone();
+        handlers.add(id, EventKind::Key, capture, Box::new(move |_, context| {
+            log.borrow_mut().push((context.current.clone(), context.phase, name));
+        }));
+    }
+
+    fn entry(id: &str, phase: Phase, name: &'static str) -> (String, Phase, &'static str) {
+        (id.to_string(), phase, name)
+    }
+
+    #[test]
+    fn key_handlers_skip_releases() {
+        let kinds = Rc::new(RefCell::new(Vec::new()));
+        let mut handlers = Handlers::default();
+        for kind in [EventKind::Key, EventKind::KeyRelease] {
+            let kinds = kinds.clone();
+            handlers.add("main", kind, false, Box::new(move |_, _| kinds.borrow_mut().push(kind)));
+        }
+        let path = vec!["main".to_string()];
+
+        handlers.dispatch(&path, &key(InputAction::Press));
+        handlers.dispatch(&path, &key(InputAction::Hold));
+        handlers.dispatch(&path, &key(InputAction::Release));
+
+        assert_eq!(*kinds.borrow(), vec![EventKind::Key, EventKind::Key, EventKind::KeyRelease]);
+    }
+
+    #[test]
+    fn capture_runs_before_bubble() {
+        let entries: Log = Rc::default();
+        let mut handlers = Handlers::default();
+        for id in ["main", "list", "item"] {
+            log(&mut handlers, &entries, id, false, "bubble");
+            log(&mut handlers, &entries, id, true, "capture");
+        }
+
+        let context = handlers.dispatch(&path(&["main", "list", "item"]), &key(InputAction::Press));
+
+        assert_eq!(context.target, "item");
+        assert_eq!(*entries.borrow(), vec![
+            entry("main", Phase::Capture, "capture"),
+            entry("list", Phase::Capture, "capture"),
+            entry("item", Phase::Target, "capture"),
+            entry("item", Phase::Target, "bubble"),
+            entry("list", Phase::Bubble, "bubble"),
+            entry("main", Phase::Bubble, "bubble"),
+        ]);
+    }
+
+    #[test]
+    fn stop_propagation_finishes_the_current_container() {
+        let entries: Log = Rc::default();
+        let mut handlers = Handlers::default();
+        log(&mut handlers, &entries, "main", false, "main");
+        let stopped = entries.clone();
+        handlers.add("list", EventKind::Key, false, Box::new(move |_, context| {
+            stopped.borrow_mut().push((context.current.clone(), context.phase, "stop"));
+            context.stop_propagation();
+        }));
+        log(&mut handlers, &entries, "list", false, "after");
+
+        let context = handlers.dispatch(&path(&["main", "list", "item"]), &key(InputAction::Press));
+
+        assert!(context.is_propagation_stopped());
+        assert_eq!(*entries.borrow(), vec![
+            entry("list", Phase::Bubble, "stop"),
+            entry("list", Phase::Bubble, "after"),
+        ]);
+    }
+
+    #[test]
+    fn stop_propagation_in_capture_skips_the_target() {
+        let entries: Log = Rc::default();
+        let mut handlers = Handlers::default();
+        handlers.add("main", EventKind::Key, true, Box::new(|_, context| context.stop_propagation()));
+        log(&mut handlers, &entries, "item", false, "item");
+        log(&mut handlers, &entries, "main", false, "main");
+
+        handlers.dispatch(&path(&["main", "item"]), &key(InputAction::Press));
+
+        assert!(entries.borrow().is_empty());
+    }
+
+    #[test]
+    fn handlers_of_other_kinds_do_not_run() {
+        let entries: Log = Rc::default();
+        let mut handlers = Handlers::default();
+        log(&mut handlers, &entries, "main", false, "key");
+
+        handlers.dispatch(&path(&["main"]), &Event::Focus("main".to_string()));
+        handlers.dispatch(&path(&["main"]), &Event::Resize(10, 10));
+
+        assert!(entries.borrow().is_empty());
+    }
+
+    #[test]
+    fn prevent_default_keeps_tab_from_moving_focus() {
+        let mut drawer = Drawer::with_writer(Vec::new());
+        drawer.set_size(Some((20, 10)));
+        let mut rll = Rll::new(Backend::new(drawer, Listener::new().0));
+        rll.set_main_container(Container::new("main", "", vec![], vec![
+            Container::new("a", "", vec![], vec![]),
+            Container::new("b", "", vec![], vec![]),
+        ]));
+        rll.attach_style_to_id("a", vec![Style::Focusable(true)]);
+        rll.attach_style_to_id("b", vec![Style::Focusable(true)]);
+        assert!(rll.focus("a"));
+
+        rll.on("a", EventKind::Key, |event, context| {
+            if let Event::Input(input) = event {
+                if input.key_code == KeyCode::Tab {
+                    context.prevent_default();
+                }
+            }
+        });
+        let tab = || Event::Input(Input::new(KeyCode::Tab, InputAction::Press));
+
+        rll.handle_event(tab());
+        assert_eq!(rll.focused(), Some("a"));
+
+        assert!(rll.focus("b"));
+        rll.handle_event(tab());
+        assert_eq!(rll.focused(), Some("a"));
+    }
+}
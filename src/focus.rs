@@ -0,0 +1,151 @@
+use crate::{
+    layout::{Container, StyleIndex},
+    style::Style,
+};
+
+/// Class added to the focused container, so stylesheets can style it.
+pub const FOCUSED_CLASS: &str = "focused";
+
+#[derive(Default)]
+struct FocusStyle {
+    focusable: bool,
+    tab_index: u16,
+    modal: bool,
+}
+
+fn focus_style(container: &Container, styles: &StyleIndex) -> FocusStyle {
+    let mut focus_style = FocusStyle::default();
+    for style in styles.styles_for(container) {
+        match style {
+            Style::Focusable(focusable) => focus_style.focusable = focusable,
+            Style::TabIndex(tab_index) => focus_style.tab_index = tab_index,
+            Style::Modal(modal) => focus_style.modal = modal,
+            _ => {},
+        }
+    }
+    focus_style
+}
+
+/// Subtree focus is trapped in: the last modal container in tree order, or `root`.
+pub fn scope<'a>(root: &'a Container, styles: &StyleIndex) -> &'a Container {
+    let mut scope = root;
+    visit(root, &mut |container| {
+        if focus_style(container, styles).modal {
+            scope = container;
+        }
+    });
+    scope
+}
+
+/// Focusable container ids inside `scope` in Tab order: indexed ones first, then tree order.
+pub fn order(scope: &Container, styles: &StyleIndex) -> Vec<String> {
+    let mut focusable: Vec<(u16, String)> = Vec::new();
+    visit(scope, &mut |container| {
+        let focus_style = focus_style(container, styles);
+        if focus_style.focusable {
+            focusable.push((focus_style.tab_index, container.id.clone()));
+        }
+    });
+    // stable, so equal indexes keep tree order
+    focusable.sort_by_key(|(tab_index, _)| if *tab_index == 0 {u16::MAX as u32 + 1} else {*tab_index as u32});
+    focusable.into_iter().map(|(_, id)| id).collect()
+}
+
+/// The id after `current` in `order`, wrapping around. Starts at either end when
+/// `current` is not part of the order.
+pub fn next(order: &[String], current: Option<&str>, backward: bool) -> Option<String> {
+    if order.is_empty() {
+        return None;
+    }
+    let position = current.and_then(|current| order.iter().position(|id| id == current));
+    let index = match (position, backward) {
+        (Some(i), false) => (i + 1) % order.len(),
+        (Some(i), true) => (i + order.len() - 1) % order.len(),
+        (None, false) => 0,
+        (None, true) => order.len() - 1,
+    };
+    Some(order[index].clone())
+}
+
+fn visit<'a>(container: &'a Container, f: &mut impl FnMut(&'a Container)) {
+    f(container);
+    for child in container.childs() {
+        visit(child, f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{layout::StyleLink, theme::Theme};
+
+    fn link(id: &str, style: Vec<Style>) -> StyleLink {
+        StyleLink {id: Some(id.to_string()), class: None, style}
+    }
+
+    fn leaf(id: &str) -> Container {
+        Container::new(id, "", vec![], vec![])
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn tree() -> Container {
+        Container::new("main", "", vec![], vec![
+            Container::new("form", "", vec![], vec![leaf("name"), leaf("mail")]),
+            leaf("ok"),
+            Container::new("dialog", "", vec![], vec![leaf("yes"), leaf("no")]),
+        ])
+    }
+
+    #[test]
+    fn order_puts_indexed_containers_first_then_tree_order() {
+        let styles = StyleIndex::new(&[
+            link("name", vec![Style::Focusable(true)]),
+            link("mail", vec![Style::Focusable(true), Style::TabIndex(2)]),
+            link("ok", vec![Style::Focusable(true), Style::TabIndex(1)]),
+            link("yes", vec![Style::Focusable(true)]),
+            link("no", vec![Style::Focusable(true), Style::TabIndex(2)]),
+            link("form", vec![Style::TabIndex(1)]),
+        ], Theme::default());
+
+        assert_eq!(order(&tree(), &styles), ids(&["ok", "mail", "no", "name", "yes"]));
+    }
+
+    #[test]
+    fn next_wraps_around() {
+        let order = ids(&["a", "b", "c"]);
+
+        assert_eq!(next(&order, Some("b"), false).as_deref(), Some("c"));
+        assert_eq!(next(&order, Some("c"), false).as_deref(), Some("a"));
+        assert_eq!(next(&order, Some("a"), true).as_deref(), Some("c"));
+        assert_eq!(next(&order, None, false).as_deref(), Some("a"));
+        assert_eq!(next(&order, Some("gone"), true).as_deref(), Some("c"));
+        assert_eq!(next(&[], Some("a"), false), None);
+    }
+
+    #[test]
+    fn scope_is_the_last_modal_in_tree_order() {
+        let tree = tree();
+        assert_eq!(scope(&tree, &StyleIndex::default()).id, "main");
+
+        let styles = StyleIndex::new(&[
+            link("form", vec![Style::Modal(true)]),
+            link("dialog", vec![Style::Modal(true)]),
+        ], Theme::default());
+        assert_eq!(scope(&tree, &styles).id, "dialog");
+    }
+
+    #[test]
+    fn order_is_limited_to_the_scope() {
+        let styles = StyleIndex::new(&[
+            link("name", vec![Style::Focusable(true)]),
+            link("yes", vec![Style::Focusable(true)]),
+            link("dialog", vec![Style::Modal(true)]),
+        ], Theme::default());
+        let tree = tree();
+
+        assert_eq!(order(scope(&tree, &styles), &styles), ids(&["yes"]));
+    }
+}
@@ -0,0 +1,460 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::backends::backend::Key;
+
+/// How long a bound prefix waits for the rest of a sequence by default.
+pub const SEQUENCE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Prefix of the class `Rll` puts on `main_container` for the current mode, e.g. `mode-insert`.
+pub const MODE_CLASS_PREFIX: &str = "mode-";
+
+/// Identifies a sequence binding, so it can be unbound again. Unique across all keymaps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BindingId(u64);
+
+impl BindingId {
+    fn next() -> Self {
+        // shared by every keymap, so an id from one layer never matches a binding of another
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        BindingId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+struct Binding {
+    id: BindingId,
+    sequence: Vec<Key>,
+    callback: Box<dyn FnMut()>,
+}
+
+/// Bindings for key sequences like `Ctrl-x Ctrl-s` or `g g`.
+///
+/// When a sequence is bound and also a prefix of a longer one, the keymap waits for
+/// the next key: if it continues the longer binding that one is matched, otherwise
+/// (or after the timeout) the shorter binding fires.
+pub struct Keymap {
+    bindings: Vec<Binding>,
+    pending: Vec<Key>,
+    pending_since: Option<Instant>,
+    timeout: Duration,
+    fall_through: bool,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Keymap {
+    pub fn new() -> Self {
+        Keymap {
+            bindings: vec![],
+            pending: vec![],
+            pending_since: None,
+            timeout: SEQUENCE_TIMEOUT,
+            fall_through: true,
+        }
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Whether keys this keymap does not match go on to the layers below it in `Modes`.
+    /// Enabled by default, an insert mode would disable it to keep normal mode bindings out.
+    pub fn set_fall_through(&mut self, fall_through: bool) {
+        self.fall_through = fall_through;
+    }
+
+    pub fn falls_through(&self) -> bool {
+        self.fall_through
+    }
+
+    /// Binds a sequence, replacing an earlier binding of the same sequence.
+    pub fn bind<F>(&mut self, sequence: &[Key], callback: F) -> BindingId
+    where
+        F: FnMut() + 'static,
+    {
+        let id = BindingId::next();
+        self.bindings.retain(|binding| binding.sequence != sequence);
+        self.bindings.push(Binding {
+            id,
+            sequence: sequence.to_vec(),
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    pub fn unbind(&mut self, id: BindingId) -> bool {
+        let len = self.bindings.len();
+        self.bindings.retain(|binding| binding.id != id);
+        self.bindings.len() != len
+    }
+
+    /// Keys typed so far of a sequence that is not complete yet.
+    pub fn pending(&self) -> &[Key] {
+        &self.pending
+    }
+
+    /// Remaining keys of every binding the pending keys can still complete, for hint displays.
+    pub fn continuations(&self) -> Vec<&[Key]> {
+        if self.pending.is_empty() {
+            return vec![];
+        }
+        self.bindings
+            .iter()
+            .filter(|binding| binding.sequence.len() > self.pending.len() && binding.sequence.starts_with(&self.pending))
+            .map(|binding| &binding.sequence[self.pending.len()..])
+            .collect()
+    }
+
+    /// Matches a pressed key, returns `false` if the key is not part of any binding.
+    pub fn feed(&mut self, key: Key) -> bool {
+        self.expire();
+        let mut sequence = self.pending.clone();
+        sequence.push(key);
+
+        loop {
+            let longer = self.bindings
+                .iter()
+                .any(|binding| binding.sequence.len() > sequence.len() && binding.sequence.starts_with(&sequence));
+            if longer {
+                self.pending = sequence;
+                self.pending_since = Some(Instant::now());
+                return true;
+            }
+            if self.fire(&sequence) {
+                self.clear_pending();
+                return true;
+            }
+            if self.pending.is_empty() {
+                return false;
+            }
+            // the key breaks the pending sequence, finish the prefix and match the key on its own
+            let prefix = std::mem::take(&mut self.pending);
+            self.fire(&prefix);
+            self.clear_pending();
+            sequence = vec![key];
+        }
+    }
+
+    /// Resolves a pending prefix whose timeout ran out, call it periodically.
+    pub fn tick(&mut self) {
+        self.expire();
+    }
+
+    fn expire(&mut self) {
+        let expired = self.pending_since.is_some_and(|since| since.elapsed() >= self.timeout);
+        if expired {
+            let prefix = std::mem::take(&mut self.pending);
+            self.fire(&prefix);
+            self.clear_pending();
+        }
+    }
+
+    fn fire(&mut self, sequence: &[Key]) -> bool {
+        match self.bindings.iter_mut().find(|binding| binding.sequence == sequence) {
+            Some(binding) => {
+                (binding.callback)();
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn clear_pending(&mut self) {
+        self.pending.clear();
+        self.pending_since = None;
+    }
+}
+
+/// Shared stack of active mode names, bindings can hold a clone to switch modes.
+#[derive(Clone, Default)]
+pub struct ModeHandle(Rc<RefCell<Vec<String>>>);
+
+impl ModeHandle {
+    pub fn push(&self, mode: &str) {
+        self.0.borrow_mut().push(mode.to_string());
+    }
+
+    pub fn pop(&self) -> Option<String> {
+        self.0.borrow_mut().pop()
+    }
+
+    /// Replaces the topmost mode, or pushes it when no mode is active.
+    pub fn set(&self, mode: &str) {
+        let mut stack = self.0.borrow_mut();
+        stack.pop();
+        stack.push(mode.to_string());
+    }
+
+    /// The topmost mode.
+    pub fn current(&self) -> Option<String> {
+        self.0.borrow().last().cloned()
+    }
+
+    /// Active modes, the topmost last.
+    pub fn stack(&self) -> Vec<String> {
+        self.0.borrow().clone()
+    }
+}
+
+/// Named keymap layers (`normal`, `insert`, `command`, ...) that are pushed and popped.
+///
+/// Keys are matched against the active layers from the topmost down until one
+/// matches or a layer does not fall through.
+#[derive(Default)]
+pub struct Modes {
+    layers: HashMap<String, Keymap>,
+    handle: ModeHandle,
+}
+
+impl Modes {
+    pub fn new() -> Self {
+        Modes::default()
+    }
+
+    /// The keymap of a mode, created empty on first use.
+    pub fn layer(&mut self, mode: &str) -> &mut Keymap {
+        self.layers.entry(mode.to_string()).or_default()
+    }
+
+    /// A handle to switch modes from inside bindings, e.g. `move || modes.push("insert")`.
+    pub fn handle(&self) -> ModeHandle {
+        self.handle.clone()
+    }
+
+    pub fn push(&self, mode: &str) {
+        self.handle.push(mode);
+    }
+
+    pub fn pop(&self) -> Option<String> {
+        self.handle.pop()
+    }
+
+    pub fn set(&self, mode: &str) {
+        self.handle.set(mode);
+    }
+
+    pub fn current(&self) -> Option<String> {
+        self.handle.current()
+    }
+
+    /// Pending keys of the topmost active layer that is in the middle of a sequence.
+    pub fn pending(&self) -> &[Key] {
+        self.handle
+            .stack()
+            .iter()
+            .rev()
+            .filter_map(|mode| self.layers.get(mode))
+            .map(|layer| layer.pending())
+            .find(|pending| !pending.is_empty())
+            .unwrap_or(&[])
+    }
+
+    /// Matches a pressed key against the active layers, returns `false` if none matched it.
+    pub fn feed(&mut self, key: Key) -> bool {
+        // the stack is copied, bindings may switch modes while it is walked
+        for mode in self.handle.stack().iter().rev() {
+            let Some(layer) = self.layers.get_mut(mode) else {
+                continue;
+            };
+            if layer.feed(key) {
+                return true;
+            }
+            if !layer.falls_through() {
+                break;
+            }
+        }
+        false
+    }
+
+    /// Whether unmatched keys reach what is below the active layers.
+    pub fn falls_through(&self) -> bool {
+        for mode in self.handle.stack().iter().rev() {
+            if let Some(layer) = self.layers.get(mode) {
+                if !layer.falls_through() {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    pub fn tick(&mut self) {
+        self.layers.values_mut().for_each(Keymap::tick);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::sleep;
+
+    use super::*;
+    use crate::backends::backend::KeyCode;
+
+    fn key(char: char) -> Key {
+        Key::new(KeyCode::Char(char))
+    }
+
+    fn keys(chars: &str) -> Vec<Key> {
+        chars.chars().map(key).collect()
+    }
+
+    /// Binds every sequence to a callback logging its name.
+    fn keymap(sequences: &[&'static str]) -> (Keymap, Rc<RefCell<Vec<&'static str>>>) {
+        let fired = Rc::new(RefCell::new(Vec::new()));
+        let mut keymap = Keymap::new();
+        for &sequence in sequences {
+            let fired = fired.clone();
+            keymap.bind(&keys(sequence), move || fired.borrow_mut().push(sequence));
+        }
+        (keymap, fired)
+    }
+
+    #[test]
+    fn single_key_fires_at_once() {
+        let (mut keymap, fired) = keymap(&["a"]);
+        assert!(keymap.feed(key('a')));
+        assert!(!keymap.feed(key('b')));
+        assert_eq!(*fired.borrow(), vec!["a"]);
+        assert!(keymap.pending().is_empty());
+    }
+
+    #[test]
+    fn prefix_waits_for_longer_binding() {
+        let (mut keymap, fired) = keymap(&["g", "gg"]);
+        assert!(keymap.feed(key('g')));
+        assert!(fired.borrow().is_empty());
+        assert_eq!(keymap.pending(), keys("g"));
+        assert!(keymap.feed(key('g')));
+        assert_eq!(*fired.borrow(), vec!["gg"]);
+        assert!(keymap.pending().is_empty());
+    }
+
+    #[test]
+    fn breaking_key_fires_prefix_then_itself() {
+        let (mut keymap, fired) = keymap(&["g", "gg", "j"]);
+        keymap.feed(key('g'));
+        assert!(keymap.feed(key('j')));
+        assert_eq!(*fired.borrow(), vec!["g", "j"]);
+        assert!(keymap.pending().is_empty());
+    }
+
+    #[test]
+    fn breaking_key_without_binding_is_unmatched() {
+        let (mut keymap, fired) = keymap(&["ab"]);
+        keymap.feed(key('a'));
+        assert!(!keymap.feed(key('x')));
+        assert!(fired.borrow().is_empty());
+        assert!(keymap.pending().is_empty());
+    }
+
+    #[test]
+    fn timeout_fires_pending_prefix() {
+        let (mut keymap, fired) = keymap(&["g", "gg"]);
+        keymap.set_timeout(Duration::from_millis(10));
+        keymap.feed(key('g'));
+        keymap.tick();
+        assert!(fired.borrow().is_empty());
+
+        sleep(Duration::from_millis(20));
+        keymap.tick();
+        assert_eq!(*fired.borrow(), vec!["g"]);
+        assert!(keymap.pending().is_empty());
+    }
+
+    #[test]
+    fn timeout_drops_unbound_prefix() {
+        let (mut keymap, fired) = keymap(&["gg"]);
+        keymap.set_timeout(Duration::from_millis(10));
+        keymap.feed(key('g'));
+        sleep(Duration::from_millis(20));
+        // the expired prefix is dropped, the key starts a new sequence
+        assert!(keymap.feed(key('g')));
+        assert!(fired.borrow().is_empty());
+        assert_eq!(keymap.pending(), keys("g"));
+    }
+
+    #[test]
+    fn continuations_of_pending_keys() {
+        let (mut keymap, _) = keymap(&["gg", "gj", "x"]);
+        assert!(keymap.continuations().is_empty());
+        keymap.feed(key('g'));
+        let mut continuations = keymap.continuations();
+        continuations.sort_by_key(|sequence| format!("{:?}", sequence));
+        assert_eq!(continuations, vec![&keys("g")[..], &keys("j")[..]]);
+    }
+
+    #[test]
+    fn bind_replaces_and_unbind_removes() {
+        let (mut keymap, fired) = keymap(&["a"]);
+        let fired_b = fired.clone();
+        let id = keymap.bind(&keys("a"), move || fired_b.borrow_mut().push("b"));
+        keymap.feed(key('a'));
+        assert_eq!(*fired.borrow(), vec!["b"]);
+        assert!(keymap.unbind(id));
+        assert!(!keymap.unbind(id));
+        assert!(!keymap.feed(key('a')));
+    }
+
+    #[test]
+    fn modes_match_topmost_layer_first() {
+        let fired = Rc::new(RefCell::new(Vec::new()));
+        let mut modes = Modes::new();
+        for mode in ["normal", "visual"] {
+            let fired = fired.clone();
+            modes.layer(mode).bind(&keys("d"), move || fired.borrow_mut().push(mode));
+        }
+        let fired_normal = fired.clone();
+        modes.layer("normal").bind(&keys("x"), move || fired_normal.borrow_mut().push("x"));
+        modes.push("normal");
+        modes.push("visual");
+
+        assert!(modes.feed(key('d')));
+        assert!(modes.feed(key('x')));
+        assert_eq!(*fired.borrow(), vec!["visual", "x"]);
+    }
+
+    #[test]
+    fn opaque_layer_stops_fall_through() {
+        let mut modes = Modes::new();
+        modes.layer("normal").bind(&keys("x"), || {});
+        modes.layer("insert").set_fall_through(false);
+        modes.layer("command");
+        modes.push("normal");
+        assert!(modes.falls_through());
+
+        modes.push("insert");
+        assert!(!modes.feed(key('x')));
+        assert!(!modes.falls_through());
+
+        // a transparent layer on top does not let keys past the opaque one below it
+        modes.push("command");
+        assert!(!modes.feed(key('x')));
+        assert!(!modes.falls_through());
+
+        modes.pop();
+        modes.pop();
+        assert!(modes.feed(key('x')));
+        assert!(modes.falls_through());
+    }
+
+    #[test]
+    fn binding_ids_are_unique_across_keymaps() {
+        let mut modes = Modes::new();
+        let normal = modes.layer("normal").bind(&keys("x"), || {});
+        let insert = modes.layer("insert").bind(&keys("x"), || {});
+        assert_ne!(normal, insert);
+
+        assert!(!modes.layer("insert").unbind(normal));
+        assert!(modes.layer("normal").unbind(normal));
+        assert!(modes.layer("insert").unbind(insert));
+    }
+}
@@ -1,10 +1,55 @@
-use crate::{backends::traits::{BackendTrait, DrawerTrair}, style::Style};
+use std::{
+    collections::{HashMap, HashSet}, 
+    sync::mpsc::{Receiver, RecvTimeoutError}, 
+    time::Duration,
+};
+
+use crate::{
+    backends::{backend::{Event, InputAction, KeyCode, MouseEvent}, traits::BackendTrait}, 
+    events::{EventContext, EventKind, Handlers}, 
+    focus, 
+    keymap::MODE_CLASS_PREFIX, 
+    span::Span, 
+    style::Style, 
+    stylesheet::{StylesheetError, StylesheetWatcher},
+    theme::Theme,
+};
+
+/// How often `Rll::run` redraws without input, keeps animated content moving.
+const FRAME_INTERVAL: Duration = Duration::from_millis(50);
 
 pub struct Rll<T:BackendTrait> {
     title: String,
     pub backend: T,
     pub main_container: Option<Container>,
     style_links: Vec<StyleLink>,
+    /// Links loaded from the watched stylesheet, kept apart from the ones attached in code.
+    stylesheet_links: Vec<StyleLink>,
+    stylesheet_watcher: Option<StylesheetWatcher>,
+    stylesheet_error: Option<StylesheetError>,
+    theme: Theme,
+    style_index: StyleIndex,
+    dirty: Dirty,
+    focused: Option<String>,
+    handlers: Handlers,
+    mode: Option<String>,
+}
+
+/// Changes made to the container tree since the last render.
+#[derive(Clone, Default)]
+pub struct Dirty {
+    /// The whole tree has to be laid out again, e.g. after a style change.
+    pub all: bool,
+    /// Containers whose subtree has to be laid out again inside their previous geometry.
+    pub layout: HashSet<String>,
+    /// Containers whose content changed, their geometry is unchanged.
+    pub contaiment: HashSet<String>,
+}
+
+impl Dirty {
+    pub fn is_clean(&self) -> bool {
+        !self.all && self.layout.is_empty() && self.contaiment.is_empty()
+    }
 }
 
 impl<T: BackendTrait> Rll<T> {
@@ -14,6 +59,15 @@ impl<T: BackendTrait> Rll<T> {
             backend,
             main_container: None,
             style_links: vec![],
+            stylesheet_links: vec![],
+            stylesheet_watcher: None,
+            stylesheet_error: None,
+            theme: Theme::dark(),
+            style_index: StyleIndex::default(),
+            dirty: Dirty {all: true, ..Dirty::default()},
+            focused: None,
+            handlers: Handlers::default(),
+            mode: None,
         }
     }
 
@@ -23,6 +77,45 @@ impl<T: BackendTrait> Rll<T> {
     
     pub fn set_main_container(&mut self, container: Container) {
         self.main_container = Some(container);
+        if let (Some(container), Some(mode)) = (&mut self.main_container, &self.mode) {
+            container.add_class(&format!("{}{}", MODE_CLASS_PREFIX, mode));
+        }
+        self.dirty.all = true;
+    }
+
+    /// Current input mode, synced from the backend by `handle_event` and `run`.
+    pub fn mode(&self) -> Option<&str> {
+        self.mode.as_deref()
+    }
+
+    /// Sets the input mode, `main_container` gets a `mode-<name>` class for stylesheets.
+    /// The tree is only laid out again if some style is attached to a `mode-` class.
+    pub fn set_mode(&mut self, mode: Option<&str>) {
+        if self.mode.as_deref() == mode {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.mode, mode.map(str::to_string));
+        let styled = self.stylesheet_links
+            .iter()
+            .chain(&self.style_links)
+            .any(|link| link.class.as_deref().is_some_and(|class| class.starts_with(MODE_CLASS_PREFIX)));
+        let Some(container) = &mut self.main_container else {
+            return;
+        };
+        if let Some(previous) = previous {
+            container.remove_class(&format!("{}{}", MODE_CLASS_PREFIX, previous));
+        }
+        if let Some(mode) = mode {
+            container.add_class(&format!("{}{}", MODE_CLASS_PREFIX, mode));
+        }
+        if styled {
+            self.dirty.layout.insert(container.id.clone());
+        }
+    }
+
+    /// Forces a full relayout, needed after mutating `main_container` directly.
+    pub fn invalidate(&mut self) {
+        self.dirty.all = true;
     }
 
     pub fn attach_style_to_id(&mut self, id: &str, style: Vec<Style>) {
@@ -32,7 +125,8 @@ impl<T: BackendTrait> Rll<T> {
                 class: None,
                 style,
             }
-        )
+        );
+        self.dirty.all = true;
     }
 
     pub fn attach_style_to_class(&mut self, class: &str, style: Vec<Style>) {
@@ -43,26 +137,255 @@ impl<T: BackendTrait> Rll<T> {
                 style,
             }
         );
+        self.dirty.all = true;
+    }
+
+    /// Switches the palette used to resolve `Color::Var`, applied on the next render.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.dirty.all = true;
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// Replaces the links attached in code, links from a watched stylesheet are kept.
+    pub fn set_style_links(&mut self, style_links: Vec<StyleLink>) {
+        self.style_links = style_links;
+        self.dirty.all = true;
+    }
+
+    /// Starts watching a stylesheet file, it is loaded on the next `poll_stylesheet` call.
+    pub fn watch_stylesheet(&mut self, path: &str) {
+        self.stylesheet_watcher = Some(StylesheetWatcher::new(path));
+    }
+
+    /// Stops watching and drops the styles loaded from the stylesheet.
+    pub fn unwatch_stylesheet(&mut self) {
+        self.stylesheet_watcher = None;
+        self.stylesheet_links.clear();
+        self.stylesheet_error = None;
+        self.dirty.all = true;
+    }
+
+    /// Reloads the watched stylesheet if it changed on disk and re-renders, `run` calls it
+    /// on every frame. Stylesheet rules come before links attached in code, so the latter win.
+    /// On a parse error the previous styles are kept and the error is returned.
+    pub fn poll_stylesheet(&mut self) -> Option<Result<(), StylesheetError>> {
+        let result = self.stylesheet_watcher.as_mut()?.poll()?;
+
+        Some(match result {
+            Ok(style_links) => {
+                self.stylesheet_links = style_links;
+                self.stylesheet_error = None;
+                self.dirty.all = true;
+                self.render();
+                self.display();
+                Ok(())
+            },
+            Err(error) => {
+                self.stylesheet_error = Some(error.clone());
+                Err(error)
+            },
+        })
+    }
+
+    /// Error of the last stylesheet reload, `None` once it loads again.
+    pub fn stylesheet_error(&self) -> Option<&StylesheetError> {
+        self.stylesheet_error.as_ref()
     }
 
     pub fn attach_container_by_id(&mut self, parent_container_id: &str, child_container: Container) {
         if let Some(container) = &mut self.main_container {
             Self::find_and_attach(container, parent_container_id, child_container);
+            self.dirty.layout.insert(parent_container_id.to_string());
         }
     }
 
     pub fn change_container_contaiment(&mut self, target_id: &str, new_contaiment: &str) {
         if let Some(container) = &mut self.main_container {
             Self::find_and_change_contaiment(container, target_id, new_contaiment);
+            self.dirty.contaiment.insert(target_id.to_string());
+        }
+    }
+
+    pub fn change_container_spans(&mut self, target_id: &str, new_spans: Vec<Span>) {
+        if let Some(container) = &mut self.main_container {
+            Self::find_and_change_spans(container, target_id, &new_spans);
+            self.dirty.contaiment.insert(target_id.to_string());
         }
     }
 
     pub fn remove_container_by_id(&mut self, container_id: &str) {
         if let Some(container) = &mut self.main_container {
-            Self::find_and_remove(container, container_id)
+            if let Some(parent_id) = Self::find_and_remove(container, container_id) {
+                self.dirty.layout.insert(parent_id);
+            }
+            // the focused container may have been removed along with its ancestor
+            if self.focused.as_deref().is_some_and(|id| container.find(id).is_none()) {
+                self.focused = None;
+            }
+        }
+    }
+
+    pub fn add_container_class(&mut self, target_id: &str, class: &str) {
+        if let Some(container) = &mut self.main_container {
+            if let Some(target) = container.find_mut(target_id) {
+                target.add_class(class);
+                self.mark_parent_layout(target_id);
+            }
+        }
+    }
+
+    pub fn remove_container_class(&mut self, target_id: &str, class: &str) {
+        if let Some(container) = &mut self.main_container {
+            if let Some(target) = container.find_mut(target_id) {
+                target.remove_class(class);
+                self.mark_parent_layout(target_id);
+            }
+        }
+    }
+
+    /// Class changes may resize the container, which moves its siblings too.
+    fn mark_parent_layout(&mut self, target_id: &str) {
+        let parent_id = self.main_container
+            .as_ref()
+            .and_then(|container| container.find_parent(target_id))
+            .map(|parent| parent.id.clone());
+
+        match parent_id {
+            Some(parent_id) => {self.dirty.layout.insert(parent_id);},
+            None => {self.dirty.layout.insert(target_id.to_string());},
+        }
+    }
+
+    /// Focuses a focusable container inside the current focus scope, returns whether it did.
+    pub fn focus(&mut self, id: &str) -> bool {
+        if !self.focus_order().iter().any(|focusable| focusable == id) {
+            return false;
+        }
+        self.set_focused(Some(id.to_string()));
+        true
+    }
+
+    /// The focused container. Focus outside a modal that appeared is moved into it on
+    /// the next `render` or `handle_event`.
+    pub fn focused(&self) -> Option<&str> {
+        self.focused.as_deref()
+    }
+
+    pub fn blur(&mut self) {
+        self.set_focused(None);
+    }
+
+    /// Moves focus to the next focusable container, what Tab does in `handle_event`.
+    pub fn focus_next(&mut self) {
+        let next = focus::next(&self.focus_order(), self.focused.as_deref(), false);
+        self.set_focused(next);
+    }
+
+    /// Moves focus to the previous focusable container, what BackTab does in `handle_event`.
+    pub fn focus_previous(&mut self) {
+        let previous = focus::next(&self.focus_order(), self.focused.as_deref(), true);
+        self.set_focused(previous);
+    }
+
+    /// Focusable ids in Tab order, limited to the last modal subtree in tree order if there is one.
+    fn focus_order(&mut self) -> Vec<String> {
+        if self.dirty.all {
+            self.update_style_index();
+        }
+        match &self.main_container {
+            Some(container) => focus::order(focus::scope(container, &self.style_index), &self.style_index),
+            None => vec![],
         }
     }
 
+    /// Moves focus that is outside the focus scope to the first focusable container
+    /// inside it, or blurs when there is none.
+    fn trap_focus(&mut self) {
+        if self.focused.is_none() {
+            return;
+        }
+        if self.dirty.all {
+            self.update_style_index();
+        }
+        let (Some(container), Some(focused)) = (&self.main_container, &self.focused) else {
+            return;
+        };
+        let scope = focus::scope(container, &self.style_index);
+        if scope.find(focused).is_some() {
+            return;
+        }
+        let first = focus::order(scope, &self.style_index).into_iter().next();
+        self.set_focused(first);
+    }
+
+    fn set_focused(&mut self, id: Option<String>) {
+        if self.focused == id {
+            return;
+        }
+        if let Some(previous) = self.focused.take() {
+            self.remove_container_class(&previous, focus::FOCUSED_CLASS);
+            self.dispatch(&Event::Blur(previous));
+        }
+        if let Some(id) = &id {
+            self.add_container_class(id, focus::FOCUSED_CLASS);
+        }
+        self.focused = id.clone();
+        if let Some(id) = id {
+            self.dispatch(&Event::Focus(id));
+        }
+    }
+
+    /// Registers a handler that runs when an event of `kind` reaches the container
+    /// while bubbling up from its target, the container itself included.
+    pub fn on<F>(&mut self, id: &str, kind: EventKind, handler: F)
+    where
+        F: FnMut(&Event, &mut EventContext) + 'static,
+    {
+        self.handlers.add(id, kind, false, Box::new(handler));
+    }
+
+    /// Like `on`, but runs on the way down to the target, before any bubbling handler.
+    pub fn on_capture<F>(&mut self, id: &str, kind: EventKind, handler: F)
+    where
+        F: FnMut(&Event, &mut EventContext) + 'static,
+    {
+        self.handlers.add(id, kind, true, Box::new(handler));
+    }
+
+    /// Removes every handler registered on the container.
+    pub fn off(&mut self, id: &str) {
+        self.handlers.remove(id);
+    }
+
+    /// Runs the container handlers along the path to the event target. Keys target the
+    /// focused container, or the root of the focus scope if nothing is focused.
+    fn dispatch(&mut self, event: &Event) -> EventContext {
+        let target = match event {
+            Event::Input(_) => {
+                if self.dirty.all {
+                    self.update_style_index();
+                }
+                self.focused
+                    .clone()
+                    .or_else(|| self.main_container
+                        .as_ref()
+                        .map(|container| focus::scope(container, &self.style_index).id.clone()))
+            },
+            Event::Mouse(mouse_event) => mouse_event.target.clone(),
+            Event::Focus(id) | Event::Blur(id) => Some(id.clone()),
+            Event::Resize(..) => None,
+        };
+        let path = match (&self.main_container, target) {
+            (Some(container), Some(target)) => container.path(&target),
+            _ => vec![],
+        };
+        self.handlers.dispatch(&path, event)
+    }
+
     fn find_and_attach(container: &mut Container, target_id: &str, child_container: Container) {
         if container.id == target_id {
             container.add_child(child_container);
@@ -75,7 +398,7 @@ impl<T: BackendTrait> Rll<T> {
 
     fn find_and_change_contaiment(container: &mut Container, target_id: &str, new_contaiment: &str) {
         if container.id == target_id {
-            container.contaiment = new_contaiment.to_string();
+            container.contaiment = Contaiment::Text(new_contaiment.to_string());
         } else {
             for child in &mut container.childs {
                 Self::find_and_change_contaiment(child, target_id, new_contaiment);
@@ -83,7 +406,18 @@ impl<T: BackendTrait> Rll<T> {
         }
     }
 
-    fn find_and_remove(container: &mut Container, target_id: &str) {
+    fn find_and_change_spans(container: &mut Container, target_id: &str, new_spans: &[Span]) {
+        if container.id == target_id {
+            container.contaiment = Contaiment::Spans(new_spans.to_vec());
+        } else {
+            for child in &mut container.childs {
+                Self::find_and_change_spans(child, target_id, new_spans);
+            }
+        }
+    }
+
+    /// Returns the id of the container the child was removed from.
+    fn find_and_remove(container: &mut Container, target_id: &str) -> Option<String> {
         let mut found: bool = false;
         for child in &mut container.childs {
             if child.id == target_id {
@@ -93,28 +427,143 @@ impl<T: BackendTrait> Rll<T> {
         }
         if found {
             container.remove_child(target_id);
+            Some(container.id.clone())
         } else {
+            let mut parent_id = None;
             for child in &mut container.childs {
-                Self::find_and_remove(child, target_id);
+                parent_id = parent_id.or(Self::find_and_remove(child, target_id));
             }
+            parent_id
         }
     }
 
     // backend related methods
-    pub fn init_backend(&mut self) {
+    pub fn init_backend(&mut self) -> std::io::Result<()> {
         self.backend.init()
     }
     pub fn render(&mut self) {
+        if self.dirty.all {
+            self.update_style_index();
+        }
+        // a modal may have appeared since focus was last moved
+        self.trap_focus();
         if let Some(container) = &self.main_container {
-            self.backend.render(container.clone(), self.style_links.clone());
+            self.backend.render(container, &self.style_index, &self.dirty);
+            self.dirty = Dirty::default();
         }
     }
-    pub fn display(& self) {
+    pub fn display(&mut self) {
         self.backend.display();
     }
-    pub fn close(&self) {
+    fn update_style_index(&mut self) {
+        let style_links: Vec<StyleLink> = self.stylesheet_links
+            .iter()
+            .chain(&self.style_links)
+            .cloned()
+            .collect();
+        self.style_index = StyleIndex::new(&self.theme.apply(&style_links), self.theme.clone());
+    }
+    pub fn close(&mut self) {
         self.backend.close();
     }
+
+    /// `(width, height, x, y)` of a container on the terminal in the last layout pass.
+    pub fn rect_of(&self, id: &str) -> Option<(u32, u32, u32, u32)> {
+        self.backend.rect_of(id)
+    }
+
+    /// Ids of the containers over a terminal cell in the last layout pass, topmost first.
+    pub fn containers_at(&self, x: u16, y: u16) -> Vec<String> {
+        self.backend.containers_at(x, y)
+    }
+
+    /// Dispatches an event to container handlers and applies its built-in handling: a resize
+    /// relayouts the whole tree at the new size, mouse events get their target container
+    /// and Tab/BackTab move focus unless a handler prevented it.
+    pub fn handle_event(&mut self, event: Event) -> Event {
+        // `Rll` does not feed keys to the listener, a handler passing an earlier key to
+        // `backend.listener.trigger` may have switched modes since the last sync
+        let mode = self.backend.mode();
+        self.set_mode(mode.as_deref());
+
+        let event = match event {
+            Event::Mouse(mouse_event) => Event::Mouse(self.hit_test(mouse_event)),
+            Event::Resize(columns, rows) => {
+                // the terminal may have reflowed the old frame even if the size ends up the same
+                self.dirty.all = true;
+                self.backend.invalidate();
+                Event::Resize(columns, rows)
+            },
+            event => event,
+        };
+
+        self.trap_focus();
+        let context = self.dispatch(&event);
+
+        if let Event::Input(input) = &event {
+            let repeatable = !matches!(input.action, InputAction::Release);
+            if !context.is_default_prevented() && repeatable && input.modifiers.is_empty() {
+                match input.key_code {
+                    KeyCode::Tab => self.focus_next(),
+                    KeyCode::BackTab => self.focus_previous(),
+                    _ => {},
+                }
+            }
+        }
+        event
+    }
+
+    /// Renders, then passes every event through `handle_event` to `handler` and redraws
+    /// after each batch if anything changed, until `handler` returns `false` or the
+    /// event channel closes.
+    ///
+    /// Keymaps and key callbacks only see the keys `handler` passes to
+    /// `backend.listener.trigger`, the mode they switch to is picked up before the next
+    /// event and after the batch.
+    pub fn run<F>(&mut self, events: &Receiver<Event>, mut handler: F)
+    where
+        F: FnMut(&mut Self, Event) -> bool,
+    {
+        let mode = self.backend.mode();
+        self.set_mode(mode.as_deref());
+        self.render();
+        self.display();
+
+        loop {
+            let first = match events.recv_timeout(FRAME_INTERVAL) {
+                Ok(event) => Some(event),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+            // handle everything queued up before drawing once
+            let pending: Vec<Event> = first.into_iter().chain(events.try_iter()).collect();
+            for event in pending {
+                let event = self.handle_event(event);
+                if !handler(self, event) {
+                    return;
+                }
+            }
+
+            self.backend.tick();
+            // errors are kept for `stylesheet_error`, the previous styles stay in use
+            let _ = self.poll_stylesheet();
+            let mode = self.backend.mode();
+            self.set_mode(mode.as_deref());
+            // idle frames cost nothing, the previous frame is only copied when something changed
+            if !self.dirty.is_clean() {
+                self.render();
+            }
+            if self.backend.needs_display() {
+                self.display();
+            }
+        }
+    }
+
+    /// Sets the event target to the deepest container under the cursor in the last layout.
+    pub fn hit_test(&self, mut event: MouseEvent) -> MouseEvent {
+        event.target = self.containers_at(event.column, event.row).into_iter().next();
+        event
+    }
 }
 
 #[derive(Clone)]
@@ -124,10 +573,80 @@ pub struct StyleLink {
     pub style: Vec<Style>,
 }
 
+/// Style links indexed by id and class, so matching a container does not scan every link.
+#[derive(Clone, Default)]
+pub struct StyleIndex {
+    links: Vec<StyleLink>,
+    by_id: HashMap<String, Vec<usize>>,
+    by_class: HashMap<String, Vec<usize>>,
+    /// Resolves theme variables used inside span content.
+    pub theme: Theme,
+}
+
+impl StyleIndex {
+    pub fn new(style_links: &[StyleLink], theme: Theme) -> Self {
+        let mut by_id: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut by_class: HashMap<String, Vec<usize>> = HashMap::new();
+
+        for (i, link) in style_links.iter().enumerate() {
+            if let Some(id) = &link.id {
+                by_id.entry(id.clone()).or_default().push(i);
+            }
+            if let Some(class) = &link.class {
+                by_class.entry(class.clone()).or_default().push(i);
+            }
+        }
+
+        StyleIndex {
+            links: style_links.to_vec(),
+            by_id,
+            by_class,
+            theme,
+        }
+    }
+
+    /// Styles matching the container id or one of its classes, in link order.
+    pub fn styles_for(&self, container: &Container) -> Vec<Style> {
+        let mut indexes: Vec<usize> = self.by_id
+            .get(&container.id)
+            .into_iter()
+            .chain(container.classes.iter().filter_map(|class| self.by_class.get(class)))
+            .flatten()
+            .copied()
+            .collect();
+        indexes.sort_unstable();
+        indexes.dedup();
+
+        indexes
+            .into_iter()
+            .flat_map(|i| self.links[i].style.iter().cloned())
+            .collect()
+    }
+}
+
+/// Container content, either plain (possibly pre-colored) text or styled spans.
+#[derive(Clone)]
+pub enum Contaiment {
+    Text(String),
+    Spans(Vec<Span>),
+}
+
+impl From<&str> for Contaiment {
+    fn from(text: &str) -> Self {
+        Contaiment::Text(text.to_string())
+    }
+}
+
+impl From<Vec<Span>> for Contaiment {
+    fn from(spans: Vec<Span>) -> Self {
+        Contaiment::Spans(spans)
+    }
+}
+
 #[derive(Clone)]
 pub struct Container {
     pub id: String,
-    pub contaiment: String,
+    pub contaiment: Contaiment,
     pub classes: Vec<String>,
     childs: Vec<Container>,
 }
@@ -141,19 +660,70 @@ impl Container {
     ) -> Self {
         Self {
             id: id.to_string(),
-            contaiment: contaiment.to_string(),
+            contaiment: Contaiment::Text(contaiment.to_string()),
             classes: classes.iter().map(|el|{el.to_string()}).collect(),
             childs,
         }
     }
 
-    pub fn childs(& self) -> Vec<Container> {
-        self.childs.clone()
+    pub fn with_spans(
+        id: &str,
+        spans: Vec<Span>,
+        classes: Vec<&str>,
+        childs: Vec<Container>,
+    ) -> Self {
+        Self {
+            id: id.to_string(),
+            contaiment: Contaiment::Spans(spans),
+            classes: classes.iter().map(|el|{el.to_string()}).collect(),
+            childs,
+        }
+    }
+
+    pub fn childs(& self) -> &[Container] {
+        &self.childs
+    }
+
+    /// Finds this container or one of its descendants by id.
+    pub fn find(&self, id: &str) -> Option<&Container> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.childs.iter().find_map(|child| child.find(id))
+    }
+
+    pub fn find_mut(&mut self, id: &str) -> Option<&mut Container> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.childs.iter_mut().find_map(|child| child.find_mut(id))
+    }
+
+    /// Ids from this container down to `id`, empty if `id` is not in the subtree.
+    pub fn path(&self, id: &str) -> Vec<String> {
+        if self.id == id {
+            return vec![self.id.clone()];
+        }
+        for child in &self.childs {
+            let mut path = child.path(id);
+            if !path.is_empty() {
+                path.insert(0, self.id.clone());
+                return path;
+            }
+        }
+        vec![]
+    }
+
+    pub fn find_parent(&self, id: &str) -> Option<&Container> {
+        if self.childs.iter().any(|child| child.id == id) {
+            return Some(self);
+        }
+        self.childs.iter().find_map(|child| child.find_parent(id))
     }
 
     // child container methods
     pub fn add_child(&mut self, child_container: Container) {self.childs.push(child_container);}
-    pub fn pop_child(&mut self) {if self.childs.len() > 0 {self.childs.pop();}}
+    pub fn pop_child(&mut self) {if !self.childs.is_empty() {self.childs.pop();}}
     pub fn clear_childs(&mut self) {self.childs = vec![]}
     pub fn remove_child(&mut self, id: &str) {
         for (i, child) in self.childs.clone().iter().enumerate() {
@@ -165,7 +735,158 @@ impl Container {
 
     // class methods
     pub fn add_class(&mut self, class: &str) {self.classes.push(class.to_string());}
-    pub fn pop_class(&mut self) {if self.classes.len() > 0 {self.classes.pop();}}
+    pub fn pop_class(&mut self) {if !self.classes.is_empty() {self.classes.pop();}}
     pub fn clear_classes(&mut self) {self.classes = vec![]}
+    pub fn remove_class(&mut self, class: &str) {self.classes.retain(|c| c != class);}
+
+}
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::{
+        backends::{
+            backend::Input,
+            crossterm::{backend::Backend, drawer::Drawer, listener::Listener},
+        },
+        style::Color,
+    };
+
+    type TestRll = Rll<Backend<Drawer<Vec<u8>>, Listener>>;
+
+    fn rll() -> TestRll {
+        let mut drawer = Drawer::with_writer(Vec::new());
+        drawer.set_size(Some((20, 10)));
+        let mut rll = Rll::new(Backend::new(drawer, Listener::new().0));
+        rll.set_main_container(Container::new("main", "", vec![], vec![
+            Container::new("page", "", vec![], vec![leaf("a"), leaf("b")]),
+            Container::new("dialog", "", vec![], vec![leaf("ok"), leaf("cancel")]),
+        ]));
+        for id in ["a", "b", "ok", "cancel"] {
+            rll.attach_style_to_id(id, vec![Style::Focusable(true)]);
+        }
+        rll
+    }
+
+    fn leaf(id: &str) -> Container {
+        Container::new(id, "", vec![], vec![])
+    }
+
+    fn key(key_code: KeyCode) -> Event {
+        Event::Input(Input::new(key_code, InputAction::Press))
+    }
+
+    /// Records the target of every key event reaching `main`.
+    fn key_targets(rll: &mut TestRll) -> Rc<RefCell<Vec<String>>> {
+        let targets = Rc::new(RefCell::new(Vec::new()));
+        let recorded = targets.clone();
+        rll.on("main", EventKind::Key, move |_, context| recorded.borrow_mut().push(context.target.clone()));
+        targets
+    }
 
-}
\ No newline at end of file
+    #[test]
+    fn tab_moves_focus_in_order() {
+        let mut rll = rll();
+        rll.handle_event(key(KeyCode::Tab));
+        assert_eq!(rll.focused(), Some("a"));
+        rll.handle_event(key(KeyCode::BackTab));
+        assert_eq!(rll.focused(), Some("cancel"));
+        assert!(rll.main_container.as_ref().and_then(|main| main.find("cancel")).is_some_and(|cancel| {
+            cancel.classes.iter().any(|class| class == focus::FOCUSED_CLASS)
+        }));
+    }
+
+    #[test]
+    fn modal_takes_focus_from_outside() {
+        let mut rll = rll();
+        let targets = key_targets(&mut rll);
+        assert!(rll.focus("a"));
+
+        rll.attach_style_to_id("dialog", vec![Style::Modal(true)]);
+        rll.render();
+        assert_eq!(rll.focused(), Some("ok"));
+        assert!(!rll.focus("b"));
+
+        rll.handle_event(key(KeyCode::Char('x')));
+        assert_eq!(*targets.borrow(), vec!["ok"]);
+    }
+
+    #[test]
+    fn keys_stay_inside_modal_before_render() {
+        let mut rll = rll();
+        let targets = key_targets(&mut rll);
+        assert!(rll.focus("a"));
+
+        rll.attach_style_to_id("dialog", vec![Style::Modal(true)]);
+        rll.handle_event(key(KeyCode::Char('x')));
+        assert_eq!(*targets.borrow(), vec!["ok"]);
+    }
+
+    #[test]
+    fn modal_without_focusables_blurs_and_gets_keys() {
+        let mut rll = rll();
+        let targets = key_targets(&mut rll);
+        assert!(rll.focus("ok"));
+        rll.remove_container_by_id("a");
+        rll.remove_container_by_id("b");
+        rll.attach_style_to_id("page", vec![Style::Modal(true)]);
+
+        rll.handle_event(key(KeyCode::Char('x')));
+        assert_eq!(rll.focused(), None);
+        assert_eq!(*targets.borrow(), vec!["page"]);
+    }
+
+    #[test]
+    fn removing_the_focused_container_clears_focus() {
+        let mut rll = rll();
+        assert!(rll.focus("ok"));
+        rll.remove_container_by_id("dialog");
+        assert_eq!(rll.focused(), None);
+
+        assert!(rll.focus("a"));
+        rll.remove_container_by_id("b");
+        assert_eq!(rll.focused(), Some("a"));
+    }
+
+    fn main_classes(rll: &TestRll) -> Vec<String> {
+        rll.main_container.as_ref().map(|main| main.classes.clone()).unwrap_or_default()
+    }
+
+    #[test]
+    fn set_mode_swaps_the_mode_class() {
+        let mut rll = rll();
+        rll.set_mode(Some("insert"));
+        assert_eq!(main_classes(&rll), vec!["mode-insert"]);
+        rll.set_mode(Some("normal"));
+        assert_eq!(main_classes(&rll), vec!["mode-normal"]);
+        rll.set_mode(None);
+        assert!(main_classes(&rll).is_empty());
+
+        rll.set_mode(Some("insert"));
+        rll.set_main_container(Container::new("other", "", vec![], vec![]));
+        assert_eq!(main_classes(&rll), vec!["mode-insert"]);
+    }
+
+    #[test]
+    fn set_mode_relayouts_only_for_styled_modes() {
+        let mut rll = rll();
+        rll.render();
+        rll.set_mode(Some("insert"));
+        assert!(rll.dirty.is_clean());
+
+        rll.attach_style_to_class("mode-normal", vec![Style::Foreground(Color::Red)]);
+        rll.render();
+        rll.set_mode(Some("normal"));
+        assert!(rll.dirty.layout.contains("main"));
+    }
+
+    #[test]
+    fn handle_event_syncs_the_mode() {
+        let mut rll = rll();
+        rll.backend.listener.modes.push("insert");
+        rll.handle_event(key(KeyCode::Char('x')));
+        assert_eq!(rll.mode(), Some("insert"));
+        assert_eq!(main_classes(&rll), vec!["mode-insert"]);
+    }
+}
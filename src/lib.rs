@@ -1,3 +1,12 @@
 pub mod layout;
 pub mod style;
-pub mod backends;
\ No newline at end of file
+pub mod stylesheet;
+pub mod theme;
+pub mod text;
+pub mod span;
+pub mod ansi;
+pub mod buffer;
+pub mod backends;
+pub mod focus;
+pub mod events;
+pub mod keymap;
\ No newline at end of file
@@ -0,0 +1,100 @@
+use crate::style::Color;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpanStyle {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub blink: bool,
+    pub reverse: bool,
+    pub hidden: bool,
+    pub strikethrough: bool,
+}
+
+impl SpanStyle {
+    pub fn new() -> Self {
+        SpanStyle::default()
+    }
+
+    pub fn fg(mut self, color: Color) -> Self {
+        self.foreground = Some(color);
+        self
+    }
+
+    pub fn bg(mut self, color: Color) -> Self {
+        self.background = Some(color);
+        self
+    }
+
+    pub fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    pub fn dim(mut self) -> Self {
+        self.dim = true;
+        self
+    }
+
+    pub fn italic(mut self) -> Self {
+        self.italic = true;
+        self
+    }
+
+    pub fn underline(mut self) -> Self {
+        self.underline = true;
+        self
+    }
+
+    pub fn reverse(mut self) -> Self {
+        self.reverse = true;
+        self
+    }
+
+    pub fn strikethrough(mut self) -> Self {
+        self.strikethrough = true;
+        self
+    }
+
+    /// Fills colors missing in `self` from `base`, attributes are combined.
+    pub fn over(&self, base: &SpanStyle) -> SpanStyle {
+        SpanStyle {
+            foreground: self.foreground.clone().or_else(|| base.foreground.clone()),
+            background: self.background.clone().or_else(|| base.background.clone()),
+            bold: self.bold || base.bold,
+            dim: self.dim || base.dim,
+            italic: self.italic || base.italic,
+            underline: self.underline || base.underline,
+            blink: self.blink || base.blink,
+            reverse: self.reverse || base.reverse,
+            hidden: self.hidden || base.hidden,
+            strikethrough: self.strikethrough || base.strikethrough,
+        }
+    }
+}
+
+/// A run of text sharing one style.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub style: SpanStyle,
+}
+
+impl Span {
+    pub fn raw(text: &str) -> Self {
+        Span {
+            text: text.to_string(),
+            style: SpanStyle::default(),
+        }
+    }
+
+    pub fn styled(text: &str, style: SpanStyle) -> Self {
+        Span {
+            text: text.to_string(),
+            style,
+        }
+    }
+}
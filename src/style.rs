@@ -17,6 +17,8 @@ pub enum Align {
     Left,
     Right,
     Center,
+    /// Stretches wrapped lines to the full width, the last line stays left aligned.
+    Justify,
 }
 
 #[derive(Clone)]
@@ -25,13 +27,217 @@ pub enum ContentWrap {
     NoWrap,
 }
 
+#[derive(Clone)]
+pub enum TextOverflow {
+    Clip,
+    /// Dims the last visible cells of a truncated line.
+    Fade,
+    Ellipsis,
+    EllipsisStart,
+    EllipsisMiddle,
+    /// Scrolls the line horizontally over time, requires periodic re-rendering.
+    Marquee,
+}
+
+#[derive(Clone)]
+pub enum ControlChars {
+    /// Shows control characters as Unicode control pictures, e.g. `␇`.
+    Placeholder,
+    Strip,
+}
+
+/// Handling of escape sequences in container content. Sequences that move the
+/// cursor or change terminal state are always removed.
+#[derive(Clone)]
+pub enum Escapes {
+    /// Removes every sequence, colors included, for untrusted text.
+    Strip,
+    /// Applies SGR color and attribute sequences, so pre-colored strings keep their
+    /// colors. The default.
+    Allow,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Color {
+    Reset,
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+    Ansi(u8),
+    Rgb(u8, u8, u8),
+    /// Semantic palette name, resolved through the current `Theme`.
+    Var(String),
+}
+
 #[derive(Clone)]
 pub enum Style {
     Orientation(Orientation),
     MinSize(Size),
     MaxSize(Size),
     ContentWrap(ContentWrap),
+    /// Applied to `ContentWrap::NoWrap` content wider than its container.
+    TextOverflow(TextOverflow),
+    /// Tab stop interval for tab expansion, `0` removes tabs.
+    TabWidth(u8),
+    ControlChars(ControlChars),
+    Escapes(Escapes),
     HAlign(Align),
     VAlign(Align),
+    /// Left, top, right, bottom.
     Margin(Size, Size, Size, Size),
-}
\ No newline at end of file
+    MarginLeft(Size),
+    MarginTop(Size),
+    MarginRight(Size),
+    MarginBottom(Size),
+    /// Left, top, right, bottom.
+    Padding(Size, Size, Size, Size),
+    PaddingLeft(Size),
+    PaddingTop(Size),
+    PaddingRight(Size),
+    PaddingBottom(Size),
+    Foreground(Color),
+    Background(Color),
+    /// Lets the container receive focus with `Rll::focus` and Tab traversal.
+    Focusable(bool),
+    /// Position in Tab traversal, indexed containers come first in ascending order,
+    /// `0` keeps tree order.
+    TabIndex(u16),
+    /// Traps focus inside the container subtree while it is in the tree.
+    Modal(bool),
+}
+
+/// Per-side spacing merged from a container styles.
+///
+/// Styles are applied in order, so a later side-specific style overrides
+/// only that side of an earlier `Margin`/`Padding`, and vice versa.
+#[derive(Clone)]
+pub struct Sides {
+    pub left: Size,
+    pub top: Size,
+    pub right: Size,
+    pub bottom: Size,
+}
+
+impl Sides {
+    pub fn zero() -> Self {
+        Sides {
+            left: Size::Fixed(0),
+            top: Size::Fixed(0),
+            right: Size::Fixed(0),
+            bottom: Size::Fixed(0),
+        }
+    }
+
+    pub fn margin(styles: &[Style]) -> Self {
+        let mut sides = Sides::zero();
+        for style in styles {
+            match style {
+                Style::Margin(left, top, right, bottom) => sides = Sides {
+                    left: left.clone(),
+                    top: top.clone(),
+                    right: right.clone(),
+                    bottom: bottom.clone(),
+                },
+                Style::MarginLeft(v) => sides.left = v.clone(),
+                Style::MarginTop(v) => sides.top = v.clone(),
+                Style::MarginRight(v) => sides.right = v.clone(),
+                Style::MarginBottom(v) => sides.bottom = v.clone(),
+                _ => {},
+            }
+        }
+        sides
+    }
+
+    pub fn padding(styles: &[Style]) -> Self {
+        let mut sides = Sides::zero();
+        for style in styles {
+            match style {
+                Style::Padding(left, top, right, bottom) => sides = Sides {
+                    left: left.clone(),
+                    top: top.clone(),
+                    right: right.clone(),
+                    bottom: bottom.clone(),
+                },
+                Style::PaddingLeft(v) => sides.left = v.clone(),
+                Style::PaddingTop(v) => sides.top = v.clone(),
+                Style::PaddingRight(v) => sides.right = v.clone(),
+                Style::PaddingBottom(v) => sides.bottom = v.clone(),
+                _ => {},
+            }
+        }
+        sides
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{layout::{Container, StyleIndex, StyleLink}, theme::Theme};
+
+    /// Left, top, right, bottom of fixed sides.
+    fn fixed(sides: &Sides) -> [u32; 4] {
+        [&sides.left, &sides.top, &sides.right, &sides.bottom].map(|side| match side {
+            Size::Fixed(value) => *value,
+            Size::Percent(_) => panic!("expected a fixed size"),
+        })
+    }
+
+    fn all(value: u32) -> [Size; 4] {
+        [Size::Fixed(value), Size::Fixed(value), Size::Fixed(value), Size::Fixed(value)]
+    }
+
+    #[test]
+    fn side_overrides_earlier_shorthand() {
+        let [left, top, right, bottom] = all(1);
+        let styles = [Style::Margin(left, top, right, bottom), Style::MarginLeft(Size::Fixed(4))];
+        assert_eq!(fixed(&Sides::margin(&styles)), [4, 1, 1, 1]);
+    }
+
+    #[test]
+    fn shorthand_overrides_earlier_side() {
+        let [left, top, right, bottom] = all(1);
+        let styles = [Style::MarginLeft(Size::Fixed(4)), Style::Margin(left, top, right, bottom)];
+        assert_eq!(fixed(&Sides::margin(&styles)), [1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn padding_merges_in_order() {
+        let [left, top, right, bottom] = all(2);
+        let styles = [
+            Style::PaddingTop(Size::Fixed(5)),
+            Style::Padding(left, top, right, bottom),
+            Style::PaddingRight(Size::Fixed(0)),
+            Style::MarginLeft(Size::Fixed(9)),
+        ];
+        assert_eq!(fixed(&Sides::padding(&styles)), [2, 2, 0, 2]);
+    }
+
+    #[test]
+    fn missing_sides_are_zero() {
+        assert_eq!(fixed(&Sides::margin(&[Style::MarginBottom(Size::Fixed(3))])), [0, 0, 0, 3]);
+    }
+
+    #[test]
+    fn class_rule_overrides_one_side_of_id_rule() {
+        let [left, top, right, bottom] = all(1);
+        let links = [
+            StyleLink {id: Some("box".to_string()), class: None, style: vec![Style::Margin(left, top, right, bottom)]},
+            StyleLink {id: None, class: Some("wide".to_string()), style: vec![Style::MarginLeft(Size::Fixed(4))]},
+        ];
+        let styles = StyleIndex::new(&links, Theme::default());
+        let container = Container::new("box", "", vec!["wide"], vec![]);
+        assert_eq!(fixed(&Sides::margin(&styles.styles_for(&container))), [4, 1, 1, 1]);
+    }
+}
@@ -0,0 +1,441 @@
+use std::{fmt, fs, path::{Path, PathBuf}, time::SystemTime};
+
+use crate::{
+    layout::StyleLink,
+    style::{
+        Align,
+        Color,
+        ContentWrap,
+        ControlChars,
+        Escapes,
+        Orientation,
+        Size,
+        Style,
+        TextOverflow,
+    },
+};
+
+#[derive(Debug, Clone)]
+pub struct StylesheetError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl StylesheetError {
+    fn new(line: usize, message: &str) -> Self {
+        StylesheetError {
+            line,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for StylesheetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+impl std::error::Error for StylesheetError {}
+
+/// Parses a stylesheet made of `#id` / `.class` rules into style links.
+///
+/// ```text
+/// #main, .panel {
+///     orientation: vertical;
+///     margin: 1 2 1 2;
+///     padding-left: 1;
+///     color: $accent;
+///     background: #1e1e2e;
+/// }
+/// ```
+pub fn parse(source: &str) -> Result<Vec<StyleLink>, StylesheetError> {
+    let source = strip_comments(source);
+    let mut result: Vec<StyleLink> = Vec::new();
+    let mut rest = source.as_str();
+    let mut line = 1;
+
+    loop {
+        let Some(open) = rest.find('{') else {
+            if !rest.trim().is_empty() {
+                return Err(StylesheetError::new(line + leading_lines(rest), "expected `{`"));
+            }
+            break;
+        };
+        let selectors = &rest[..open];
+        let selectors_line = line + leading_lines(selectors);
+        let Some(close) = rest[open..].find('}').map(|i| i + open) else {
+            return Err(StylesheetError::new(selectors_line, "unclosed rule, expected `}`"));
+        };
+        let body = &rest[open + 1..close];
+        let body_line = line + selectors.matches('\n').count();
+
+        let style = parse_body(body, body_line)?;
+
+        for selector in selectors.split(',') {
+            let selector = selector.trim();
+            let link = if let Some(id) = selector.strip_prefix('#') {
+                StyleLink { id: Some(id.to_string()), class: None, style: style.clone() }
+            } else if let Some(class) = selector.strip_prefix('.') {
+                StyleLink { id: None, class: Some(class.to_string()), style: style.clone() }
+            } else {
+                return Err(StylesheetError::new(
+                    selectors_line,
+                    &format!("invalid selector `{}`, expected `#id` or `.class`", selector),
+                ));
+            };
+            result.push(link);
+        }
+
+        line += rest[..close].matches('\n').count();
+        rest = &rest[close + 1..];
+    }
+
+    Ok(result)
+}
+
+/// Reads and parses the stylesheet at `path`.
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<StyleLink>, StylesheetError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|e| {
+        StylesheetError::new(0, &format!("failed to read {}: {}", path.display(), e))
+    })?;
+    parse(&source)
+}
+
+/// Polls a stylesheet file modification time and re-parses it when it changes.
+pub struct StylesheetWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl StylesheetWatcher {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        StylesheetWatcher {
+            path: path.as_ref().to_path_buf(),
+            modified: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns `None` while the file is unchanged since the previous poll.
+    pub fn poll(&mut self) -> Option<Result<Vec<StyleLink>, StylesheetError>> {
+        let modified = match fs::metadata(&self.path).and_then(|m| m.modified()) {
+            Ok(modified) => modified,
+            Err(e) => {
+                return Some(Err(StylesheetError::new(
+                    0,
+                    &format!("failed to read {}: {}", self.path.display(), e),
+                )))
+            },
+        };
+
+        if self.modified == Some(modified) {
+            return None;
+        }
+        self.modified = Some(modified);
+
+        Some(load(&self.path))
+    }
+}
+
+fn parse_body(body: &str, mut line: usize) -> Result<Vec<Style>, StylesheetError> {
+    let mut styles: Vec<Style> = Vec::new();
+
+    for declaration in body.split(';') {
+        let declaration_line = line + leading_lines(declaration);
+        line += declaration.matches('\n').count();
+
+        if declaration.trim().is_empty() {
+            continue;
+        }
+
+        let Some((name, value)) = declaration.split_once(':') else {
+            return Err(StylesheetError::new(
+                declaration_line,
+                &format!("expected `property: value`, found `{}`", declaration.trim()),
+            ));
+        };
+
+        styles.push(parse_declaration(name.trim(), value.trim())
+            .map_err(|message| StylesheetError::new(declaration_line, &message))?);
+    }
+
+    Ok(styles)
+}
+
+fn parse_declaration(name: &str, value: &str) -> Result<Style, String> {
+    match name {
+        "orientation" => match value {
+            "horizontal" => Ok(Style::Orientation(Orientation::Horizontal)),
+            "vertical" => Ok(Style::Orientation(Orientation::Vertical)),
+            _ => Err(format!("invalid orientation `{}`", value)),
+        },
+        "min-size" => Ok(Style::MinSize(parse_size(value)?)),
+        "max-size" => Ok(Style::MaxSize(parse_size(value)?)),
+        "content-wrap" => match value {
+            "wrap" => Ok(Style::ContentWrap(ContentWrap::Wrap)),
+            "nowrap" => Ok(Style::ContentWrap(ContentWrap::NoWrap)),
+            _ => Err(format!("invalid content-wrap `{}`", value)),
+        },
+        "text-overflow" => match value {
+            "clip" => Ok(Style::TextOverflow(TextOverflow::Clip)),
+            "fade" => Ok(Style::TextOverflow(TextOverflow::Fade)),
+            "ellipsis" => Ok(Style::TextOverflow(TextOverflow::Ellipsis)),
+            "ellipsis-start" => Ok(Style::TextOverflow(TextOverflow::EllipsisStart)),
+            "ellipsis-middle" => Ok(Style::TextOverflow(TextOverflow::EllipsisMiddle)),
+            "marquee" => Ok(Style::TextOverflow(TextOverflow::Marquee)),
+            _ => Err(format!("invalid text-overflow `{}`", value)),
+        },
+        "tab-width" => value
+            .parse::<u8>()
+            .map(Style::TabWidth)
+            .map_err(|_| format!("invalid tab-width `{}`", value)),
+        "control-chars" => match value {
+            "placeholder" => Ok(Style::ControlChars(ControlChars::Placeholder)),
+            "strip" => Ok(Style::ControlChars(ControlChars::Strip)),
+            _ => Err(format!("invalid control-chars `{}`", value)),
+        },
+        "escapes" => match value {
+            "strip" => Ok(Style::Escapes(Escapes::Strip)),
+            "allow" => Ok(Style::Escapes(Escapes::Allow)),
+            _ => Err(format!("invalid escapes `{}`", value)),
+        },
+        "h-align" => Ok(Style::HAlign(parse_align(value)?)),
+        "v-align" => Ok(Style::VAlign(parse_align(value)?)),
+        "margin" => {
+            let (left, top, right, bottom) = parse_sides(value)?;
+            Ok(Style::Margin(left, top, right, bottom))
+        },
+        "margin-left" => Ok(Style::MarginLeft(parse_size(value)?)),
+        "margin-top" => Ok(Style::MarginTop(parse_size(value)?)),
+        "margin-right" => Ok(Style::MarginRight(parse_size(value)?)),
+        "margin-bottom" => Ok(Style::MarginBottom(parse_size(value)?)),
+        "padding" => {
+            let (left, top, right, bottom) = parse_sides(value)?;
+            Ok(Style::Padding(left, top, right, bottom))
+        },
+        "padding-left" => Ok(Style::PaddingLeft(parse_size(value)?)),
+        "padding-top" => Ok(Style::PaddingTop(parse_size(value)?)),
+        "padding-right" => Ok(Style::PaddingRight(parse_size(value)?)),
+        "padding-bottom" => Ok(Style::PaddingBottom(parse_size(value)?)),
+        "color" => Ok(Style::Foreground(parse_color(value)?)),
+        "background" => Ok(Style::Background(parse_color(value)?)),
+        "focusable" => Ok(Style::Focusable(parse_bool(value)?)),
+        "tab-index" => value
+            .parse::<u16>()
+            .map(Style::TabIndex)
+            .map_err(|_| format!("invalid tab-index `{}`", value)),
+        "modal" => Ok(Style::Modal(parse_bool(value)?)),
+        _ => Err(format!("unknown property `{}`", name)),
+    }
+}
+
+/// Accepts color names, `#rrggbb`, ansi values `0..=255` and `$name` theme variables.
+fn parse_color(value: &str) -> Result<Color, String> {
+    if let Some(name) = value.strip_prefix('$') {
+        if name.is_empty() {
+            return Err("empty theme variable name".to_string());
+        }
+        return Ok(Color::Var(name.to_string()));
+    }
+
+    if let Some(hex) = value.strip_prefix('#') {
+        let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+        return match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Color::Rgb(r, g, b)),
+            _ => Err(format!("invalid hex color `{}`", value)),
+        };
+    }
+
+    if let Ok(v) = value.parse::<u8>() {
+        return Ok(Color::Ansi(v));
+    }
+
+    match value {
+        "reset" => Ok(Color::Reset),
+        "black" => Ok(Color::Black),
+        "red" => Ok(Color::Red),
+        "green" => Ok(Color::Green),
+        "yellow" => Ok(Color::Yellow),
+        "blue" => Ok(Color::Blue),
+        "magenta" => Ok(Color::Magenta),
+        "cyan" => Ok(Color::Cyan),
+        "white" => Ok(Color::White),
+        "bright-black" => Ok(Color::BrightBlack),
+        "bright-red" => Ok(Color::BrightRed),
+        "bright-green" => Ok(Color::BrightGreen),
+        "bright-yellow" => Ok(Color::BrightYellow),
+        "bright-blue" => Ok(Color::BrightBlue),
+        "bright-magenta" => Ok(Color::BrightMagenta),
+        "bright-cyan" => Ok(Color::BrightCyan),
+        "bright-white" => Ok(Color::BrightWhite),
+        _ => Err(format!("invalid color `{}`", value)),
+    }
+}
+
+/// Shorthand spacing: `all`, `horizontal vertical` or `left top right bottom`.
+fn parse_sides(value: &str) -> Result<(Size, Size, Size, Size), String> {
+    let sizes = value
+        .split_whitespace()
+        .map(parse_size)
+        .collect::<Result<Vec<Size>, String>>()?;
+
+    match sizes.as_slice() {
+        [all] => Ok((all.clone(), all.clone(), all.clone(), all.clone())),
+        [x, y] => Ok((x.clone(), y.clone(), x.clone(), y.clone())),
+        [left, top, right, bottom] => Ok((left.clone(), top.clone(), right.clone(), bottom.clone())),
+        _ => Err(format!("expected 1, 2 or 4 sizes, found {}", sizes.len())),
+    }
+}
+
+fn parse_size(value: &str) -> Result<Size, String> {
+    if let Some(percent) = value.strip_suffix('%') {
+        percent
+            .parse::<u8>()
+            .map(Size::Percent)
+            .map_err(|_| format!("invalid percent size `{}`", value))
+    } else {
+        value
+            .parse::<u32>()
+            .map(Size::Fixed)
+            .map_err(|_| format!("invalid size `{}`", value))
+    }
+}
+
+fn parse_align(value: &str) -> Result<Align, String> {
+    match value {
+        "top" => Ok(Align::Top),
+        "bottom" => Ok(Align::Bottom),
+        "left" => Ok(Align::Left),
+        "right" => Ok(Align::Right),
+        "center" => Ok(Align::Center),
+        "justify" => Ok(Align::Justify),
+        _ => Err(format!("invalid align `{}`", value)),
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("expected `true` or `false`, found `{}`", value)),
+    }
+}
+
+fn leading_lines(text: &str) -> usize {
+    let trimmed = text.trim_start();
+    text[..text.len() - trimmed.len()].matches('\n').count()
+}
+
+/// Replaces `/* ... */` comments with whitespace, keeping line numbers intact.
+fn strip_comments(source: &str) -> String {
+    let mut result = String::with_capacity(source.len());
+    let mut rest = source;
+
+    while let Some(start) = rest.find("/*") {
+        result.push_str(&rest[..start]);
+        let end = rest[start..].find("*/").map(|i| start + i + 2).unwrap_or(rest.len());
+        result.extend(rest[start..end].chars().filter(|c| *c == '\n'));
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_of(source: &str) -> StylesheetError {
+        parse(source).err().expect("expected a parse error")
+    }
+
+    #[test]
+    fn selectors_share_the_rule_body() {
+        let links = parse("#main, .panel { tab-width: 4; }").unwrap();
+
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].id.as_deref(), Some("main"));
+        assert_eq!(links[0].class, None);
+        assert_eq!(links[1].id, None);
+        assert_eq!(links[1].class.as_deref(), Some("panel"));
+        assert!(links.iter().all(|link| matches!(link.style[..], [Style::TabWidth(4)])));
+    }
+
+    #[test]
+    fn comments_and_empty_declarations_are_ignored() {
+        let links = parse("/* header */ .a { ; color: red; /* note */ ; }").unwrap();
+
+        assert!(matches!(links[0].style[..], [Style::Foreground(Color::Red)]));
+    }
+
+    #[test]
+    fn margin_shorthands() {
+        let links = parse(".a { margin: 1; padding: 1 50%; margin: 1 2 3 4; }").unwrap();
+
+        assert!(matches!(
+            links[0].style[..],
+            [
+                Style::Margin(Size::Fixed(1), Size::Fixed(1), Size::Fixed(1), Size::Fixed(1)),
+                Style::Padding(Size::Fixed(1), Size::Percent(50), Size::Fixed(1), Size::Percent(50)),
+                Style::Margin(Size::Fixed(1), Size::Fixed(2), Size::Fixed(3), Size::Fixed(4)),
+            ]
+        ));
+        assert!(error_of(".a { margin: 1 2 3; }").message.contains("expected 1, 2 or 4 sizes"));
+    }
+
+    #[test]
+    fn colors() {
+        let links = parse(".a { color: #ff8000; background: $accent; color: 208; color: bright-cyan; }").unwrap();
+
+        assert!(matches!(
+            &links[0].style[..],
+            [
+                Style::Foreground(Color::Rgb(255, 128, 0)),
+                Style::Background(Color::Var(name)),
+                Style::Foreground(Color::Ansi(208)),
+                Style::Foreground(Color::BrightCyan),
+            ] if name == "accent"
+        ));
+        assert!(error_of(".a { color: #fff; }").message.contains("invalid hex color"));
+    }
+
+    #[test]
+    fn errors_report_the_declaration_line() {
+        let error = error_of("#a {\n  tab-width: 2;\n\n  colour: red;\n}");
+
+        assert_eq!(error.line, 4);
+        assert_eq!(error.to_string(), "line 4: unknown property `colour`");
+    }
+
+    #[test]
+    fn errors_after_comments_keep_line_numbers() {
+        let error = error_of("/* one\n two */\n.a { color: red; }\n.b {\n color: nope;\n}");
+
+        assert_eq!(error.line, 5);
+    }
+
+    #[test]
+    fn invalid_selector() {
+        let error = error_of(".a { }\n\nmain { }");
+
+        assert_eq!(error.line, 3);
+        assert!(error.message.contains("invalid selector `main`"));
+    }
+
+    #[test]
+    fn unclosed_rule_and_trailing_text() {
+        assert_eq!(error_of(".a { color: red;\n").message, "unclosed rule, expected `}`");
+        assert_eq!(error_of(".a { }\n\n.b").line, 3);
+        assert!(error_of(".a { color }").message.contains("expected `property: value`"));
+    }
+}
@@ -0,0 +1,567 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    span::{Span, SpanStyle},
+    style::{ControlChars, TextOverflow},
+};
+
+pub const ELLIPSIS: &str = "…";
+pub const MARQUEE_GAP: usize = 3;
+pub const FADE_CELLS: usize = 3;
+pub const TAB_WIDTH: u8 = 8;
+
+/// Display width of a string in terminal cells, double-width CJK and emoji count as 2.
+pub fn width(text: &str) -> usize {
+    text.graphemes(true).map(grapheme_width).sum()
+}
+
+/// Display width of a single grapheme cluster.
+pub fn grapheme_width(grapheme: &str) -> usize {
+    grapheme.width()
+}
+
+pub fn spans_width(spans: &[Span]) -> usize {
+    spans.iter().map(|span| width(&span.text)).sum()
+}
+
+/// Takes leading graphemes while they fit into `width` cells.
+pub fn take_width(text: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, grapheme) in text.grapheme_indices(true) {
+        used += grapheme_width(grapheme);
+        if used > width {
+            return &text[..i];
+        }
+    }
+    text
+}
+
+/// Takes trailing graphemes while they fit into `width` cells.
+pub fn take_width_end(text: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, grapheme) in text.grapheme_indices(true).rev() {
+        used += grapheme_width(grapheme);
+        if used > width {
+            return &text[i + grapheme.len()..];
+        }
+    }
+    text
+}
+
+/// Splits spans into lines at `\n`, a preceding `\r` is dropped.
+pub fn lines(spans: &[Span]) -> Vec<Vec<Span>> {
+    let mut lines: Vec<Vec<Span>> = vec![vec![]];
+
+    for span in spans {
+        for (i, part) in span.text.split('\n').enumerate() {
+            if i > 0 {
+                if let Some(line) = lines.last_mut() {
+                    if let Some(last) = line.last_mut() {
+                        if last.text.ends_with('\r') {
+                            last.text.pop();
+                        }
+                    }
+                }
+                lines.push(vec![]);
+            }
+            if !part.is_empty() {
+                if let Some(line) = lines.last_mut() {
+                    line.push(Span::styled(part, span.style.clone()));
+                }
+            }
+        }
+    }
+
+    lines
+}
+
+/// Expands tabs to tab stops and replaces or strips control characters of a single line,
+/// so the printed text can never move the cursor.
+pub fn sanitize(line: &[Span], tab_width: u8, control: &ControlChars) -> Vec<Span> {
+    let tab_width = tab_width as usize;
+    let mut result: Vec<Span> = Vec::new();
+    let mut column = 0;
+
+    for span in line {
+        let mut text = String::with_capacity(span.text.len());
+
+        for grapheme in span.text.graphemes(true) {
+            if grapheme == "\t" {
+                let spaces = if tab_width == 0 {0} else {tab_width - column % tab_width};
+                text.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            } else if grapheme.chars().any(char::is_control) {
+                for c in grapheme.chars() {
+                    if !c.is_control() {
+                        text.push(c);
+                        column += width(c.encode_utf8(&mut [0; 4]));
+                    } else if let ControlChars::Placeholder = control {
+                        text.push(control_picture(c));
+                        column += 1;
+                    }
+                }
+            } else {
+                text.push_str(grapheme);
+                column += grapheme_width(grapheme);
+            }
+        }
+
+        if !text.is_empty() {
+            result.push(Span::styled(&text, span.style.clone()));
+        }
+    }
+
+    result
+}
+
+fn control_picture(c: char) -> char {
+    match c as u32 {
+        v @ 0x00..=0x1f => char::from_u32(0x2400 + v).unwrap_or(char::REPLACEMENT_CHARACTER),
+        0x7f => '\u{2421}',
+        _ => char::REPLACEMENT_CHARACTER,
+    }
+}
+
+/// Fits a single line into `width` cells according to the overflow mode.
+///
+/// `tick` is the marquee scroll offset and is ignored by the other modes.
+pub fn overflow(spans: &[Span], width: usize, mode: &TextOverflow, tick: u64) -> Vec<Span> {
+    if spans_width(spans) <= width {
+        return spans.to_vec();
+    }
+    if width == 0 {
+        return vec![];
+    }
+
+    let cells = cells(spans);
+    let ellipsis_style = |i: usize| cells.get(i).map(|c| c.1.clone()).unwrap_or_default();
+
+    let result = match mode {
+        TextOverflow::Clip => take_cells(&cells, width),
+        TextOverflow::Fade => {
+            let mut result = take_cells(&cells, width);
+            let faded = take_cells_end(&result, FADE_CELLS).len();
+            let start = result.len() - faded;
+            for cell in &mut result[start..] {
+                cell.1.dim = true;
+            }
+            result
+        },
+        TextOverflow::Ellipsis => {
+            let mut result = take_cells(&cells, width - 1);
+            result.push((ELLIPSIS, ellipsis_style(result.len())));
+            result
+        },
+        TextOverflow::EllipsisStart => {
+            let tail = take_cells_end(&cells, width - 1);
+            let mut result = vec![(ELLIPSIS, ellipsis_style(cells.len() - tail.len()))];
+            result.extend(tail);
+            result
+        },
+        TextOverflow::EllipsisMiddle => {
+            let head = take_cells(&cells, (width - 1) - (width - 1) / 2);
+            let tail = take_cells_end(&cells, (width - 1) / 2);
+            let mut result = head;
+            result.push((ELLIPSIS, ellipsis_style(result.len())));
+            result.extend(tail);
+            result
+        },
+        TextOverflow::Marquee => {
+            let cycle = cells.len() + MARQUEE_GAP;
+            let offset = (tick % cycle as u64) as usize;
+            let looped: Vec<(&str, SpanStyle)> = cells
+                .iter()
+                .cloned()
+                .chain(std::iter::repeat_n((" ", SpanStyle::default()), MARQUEE_GAP))
+                .cycle()
+                .skip(offset)
+                .take(cycle)
+                .collect();
+            take_cells(&looped, width)
+        },
+    };
+
+    join_cells(result)
+}
+
+/// Splits spans into lines of at most `width` cells, breaking at whitespace and
+/// falling back to grapheme breaks for words longer than a line. Whitespace is kept
+/// as is, leading indentation included, except where a line breaks.
+pub fn wrap(spans: &[Span], width: usize) -> Vec<Vec<Span>> {
+    let mut lines: Vec<Vec<Span>> = Vec::new();
+    if width == 0 {
+        return lines;
+    }
+
+    let mut line: Vec<(&str, SpanStyle)> = Vec::new();
+    let mut line_width = 0;
+    let cells = cells(spans);
+    let (words, trailing) = words(&cells);
+
+    for (gap, word) in words {
+        let gap_width = cells_width(&gap);
+        let word_width = cells_width(&word);
+
+        if !line.is_empty() && line_width + gap_width + word_width <= width {
+            line.extend(gap);
+            line.extend(word);
+            line_width += gap_width + word_width;
+            continue;
+        }
+
+        let mut word = word;
+        if !line.is_empty() {
+            lines.push(join_cells(std::mem::take(&mut line)));
+        } else if gap_width < width {
+            // indentation of the first line
+            word = gap.into_iter().chain(word).collect();
+        }
+        while cells_width(&word) > width {
+            let mut head = take_cells(&word, width).len();
+            if head == 0 {
+                // a single grapheme wider than the line
+                head = 1;
+            }
+            lines.push(join_cells(word.drain(..head).collect()));
+        }
+        line_width = cells_width(&word);
+        line = word;
+    }
+    if line_width + cells_width(&trailing) <= width {
+        line.extend(trailing);
+    }
+    if !line.is_empty() {
+        lines.push(join_cells(line));
+    }
+
+    lines
+}
+
+/// Distributes extra spaces between the words of a line so it spans `width` cells,
+/// leading indentation is kept.
+pub fn justify(line: &[Span], width: usize) -> Vec<Span> {
+    let cells = cells(line);
+    let (words, _) = words(&cells);
+    let indent = words.first().map(|(gap, _)| cells_width(gap)).unwrap_or(0);
+    let letters: usize = words.iter().map(|(_, word)| cells_width(word)).sum();
+    if words.len() < 2 || indent + letters + words.len() > width {
+        return line.to_vec();
+    }
+
+    let gaps = words.len() - 1;
+    let spaces = width - indent - letters;
+    let mut result: Vec<(&str, SpanStyle)> = Vec::new();
+
+    for (i, (gap, word)) in words.into_iter().enumerate() {
+        if i > 0 {
+            let separator = gap.first().map(|cell| cell.1.clone()).unwrap_or_default();
+            let size = spaces / gaps + if i - 1 < spaces % gaps {1} else {0};
+            result.extend(std::iter::repeat_n((" ", separator), size));
+        } else {
+            result.extend(gap);
+        }
+        result.extend(word);
+    }
+
+    join_cells(result)
+}
+
+/// Graphemes paired with their style, one per cell.
+type Cells<'a> = Vec<(&'a str, SpanStyle)>;
+
+/// One grapheme per cell, paired with the style of the span it came from.
+fn cells(spans: &[Span]) -> Vec<(&str, SpanStyle)> {
+    spans
+        .iter()
+        .flat_map(|span| span.text.graphemes(true).map(|g| (g, span.style.clone())))
+        .collect()
+}
+
+fn cells_width(cells: &[(&str, SpanStyle)]) -> usize {
+    cells.iter().map(|c| grapheme_width(c.0)).sum()
+}
+
+fn take_cells<'a>(cells: &[(&'a str, SpanStyle)], width: usize) -> Vec<(&'a str, SpanStyle)> {
+    let mut used = 0;
+    cells
+        .iter()
+        .take_while(|c| {
+            used += grapheme_width(c.0);
+            used <= width
+        })
+        .cloned()
+        .collect()
+}
+
+fn take_cells_end<'a>(cells: &[(&'a str, SpanStyle)], width: usize) -> Vec<(&'a str, SpanStyle)> {
+    let mut used = 0;
+    let count = cells
+        .iter()
+        .rev()
+        .take_while(|c| {
+            used += grapheme_width(c.0);
+            used <= width
+        })
+        .count();
+    cells[cells.len() - count..].to_vec()
+}
+
+/// Groups cells into words, each with the whitespace cells preceding it, and
+/// returns the whitespace after the last word separately.
+fn words<'a>(cells: &[(&'a str, SpanStyle)]) -> (Vec<(Cells<'a>, Cells<'a>)>, Cells<'a>) {
+    let mut words: Vec<(Cells, Cells)> = Vec::new();
+    let mut gap: Cells = Vec::new();
+
+    for cell in cells {
+        if cell.0.trim().is_empty() {
+            gap.push(cell.clone());
+        } else if gap.is_empty() && !words.is_empty() {
+            if let Some(last) = words.last_mut() {
+                last.1.push(cell.clone());
+            }
+        } else {
+            words.push((std::mem::take(&mut gap), vec![cell.clone()]));
+        }
+    }
+
+    (words, gap)
+}
+
+fn join_cells(cells: Vec<(&str, SpanStyle)>) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    for (grapheme, style) in cells {
+        match spans.last_mut() {
+            Some(last) if last.style == style => last.text.push_str(grapheme),
+            _ => spans.push(Span::styled(grapheme, style)),
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(lines: Vec<Vec<Span>>) -> Vec<String> {
+        lines.iter().map(|line| text(line)).collect()
+    }
+
+    fn text(line: &[Span]) -> String {
+        line.iter().map(|span| span.text.as_str()).collect()
+    }
+
+    #[test]
+    fn wrap_breaks_at_whitespace() {
+        let lines = wrap(&[Span::raw("the quick  brown fox")], 10);
+
+        assert_eq!(plain(lines), vec!["the quick", "brown fox"]);
+    }
+
+    #[test]
+    fn wrap_splits_words_longer_than_the_line() {
+        let lines = wrap(&[Span::raw("abcdefghij kl")], 4);
+
+        assert_eq!(plain(lines), vec!["abcd", "efgh", "ij", "kl"]);
+    }
+
+    #[test]
+    fn wrap_keeps_span_styles() {
+        let bold = SpanStyle::new().bold();
+        let lines = wrap(&[Span::raw("one "), Span::styled("two three", bold.clone())], 7);
+
+        // the separator takes the style of the whitespace it replaces
+        assert_eq!(lines[0], vec![Span::raw("one "), Span::styled("two", bold.clone())]);
+        assert_eq!(lines[1], vec![Span::styled("three", bold)]);
+    }
+
+    #[test]
+    fn wrap_keeps_indentation_and_inner_whitespace() {
+        let lines = wrap(&[Span::raw("    indented  text")], 20);
+
+        assert_eq!(plain(lines), vec!["    indented  text"]);
+    }
+
+    #[test]
+    fn wrap_keeps_expanded_tabs() {
+        let line = sanitize(&[Span::raw("a\tb    c")], TAB_WIDTH, &ControlChars::Placeholder);
+
+        assert_eq!(plain(wrap(&line, 20)), vec!["a       b    c"]);
+    }
+
+    #[test]
+    fn wrap_drops_whitespace_only_at_breaks() {
+        let lines = wrap(&[Span::raw("  one   two  three ")], 10);
+
+        assert_eq!(plain(lines), vec!["  one", "two  three"]);
+    }
+
+    #[test]
+    fn wrap_into_zero_width() {
+        assert!(wrap(&[Span::raw("text")], 0).is_empty());
+    }
+
+    #[test]
+    fn justify_spreads_spaces_from_the_left() {
+        let line = justify(&[Span::raw("a b c")], 8);
+
+        assert_eq!(text(&line), "a   b  c");
+    }
+
+    #[test]
+    fn justify_keeps_indentation() {
+        let line = justify(&[Span::raw("  a b c")], 9);
+
+        assert_eq!(text(&line), "  a  b  c");
+    }
+
+    #[test]
+    fn justify_leaves_single_words_and_full_lines() {
+        assert_eq!(text(&justify(&[Span::raw("word")], 8)), "word");
+        assert_eq!(text(&justify(&[Span::raw("ab cd")], 5)), "ab cd");
+    }
+
+    #[test]
+    fn overflow_fitting_text_is_unchanged() {
+        let line = [Span::raw("short")];
+
+        assert_eq!(overflow(&line, 5, &TextOverflow::Ellipsis, 0), line.to_vec());
+    }
+
+    #[test]
+    fn overflow_modes() {
+        let line = [Span::raw("abcdefgh")];
+        let fit = |mode: TextOverflow| text(&overflow(&line, 5, &mode, 0));
+
+        assert_eq!(fit(TextOverflow::Clip), "abcde");
+        assert_eq!(fit(TextOverflow::Ellipsis), "abcd…");
+        assert_eq!(fit(TextOverflow::EllipsisStart), "…efgh");
+        assert_eq!(fit(TextOverflow::EllipsisMiddle), "ab…gh");
+    }
+
+    #[test]
+    fn overflow_fade_dims_the_last_cells() {
+        let line = overflow(&[Span::raw("abcdefgh")], 5, &TextOverflow::Fade, 0);
+
+        assert_eq!(line, vec![Span::raw("ab"), Span::styled("cde", SpanStyle::new().dim())]);
+    }
+
+    #[test]
+    fn overflow_marquee_scrolls_with_the_tick() {
+        let line = [Span::raw("abcdefgh")];
+        let at = |tick| text(&overflow(&line, 5, &TextOverflow::Marquee, tick));
+
+        assert_eq!(at(0), "abcde");
+        assert_eq!(at(6), "gh   ");
+        assert_eq!(at(9), "  abc");
+        assert_eq!(at(8 + MARQUEE_GAP as u64), "abcde");
+        assert_eq!(at(8 + MARQUEE_GAP as u64 + 1), "bcdef");
+    }
+
+    #[test]
+    fn width_counts_wide_and_zero_width_graphemes() {
+        assert_eq!(width("日本語"), 6);
+        assert_eq!(width("e\u{301}"), 1);
+        assert_eq!(width("a\u{200b}b"), 2);
+        assert_eq!(width("👩‍🔬"), 2);
+    }
+
+    #[test]
+    fn take_width_never_splits_a_wide_grapheme() {
+        assert_eq!(take_width("日本語", 3), "日");
+        assert_eq!(take_width_end("日本語", 5), "本語");
+        assert_eq!(take_width("e\u{301}x", 1), "e\u{301}");
+    }
+
+    #[test]
+    fn wrap_cjk_by_display_width() {
+        let lines = wrap(&[Span::raw("日本語のテキスト")], 5);
+
+        assert_eq!(plain(lines.clone()), vec!["日本", "語の", "テキ", "スト"]);
+        assert!(lines.iter().all(|line| spans_width(line) <= 5));
+    }
+
+    #[test]
+    fn wrap_keeps_combining_marks_with_their_base() {
+        let lines = wrap(&[Span::raw("cafe\u{301} cafe\u{301}")], 4);
+
+        assert_eq!(plain(lines), vec!["cafe\u{301}", "cafe\u{301}"]);
+    }
+
+    #[test]
+    fn wrap_grapheme_wider_than_the_line() {
+        assert_eq!(plain(wrap(&[Span::raw("日本")], 1)), vec!["日", "本"]);
+    }
+
+    #[test]
+    fn ellipsis_on_wide_text() {
+        let line = overflow(&[Span::raw("日本語テキスト")], 6, &TextOverflow::Ellipsis, 0);
+
+        assert_eq!(text(&line), "日本…");
+        assert!(spans_width(&line) <= 6);
+    }
+
+    #[test]
+    fn lines_split_at_newlines_across_spans() {
+        let bold = SpanStyle::new().bold();
+        let lines = lines(&[Span::raw("one\ntw"), Span::styled("o\n\nthree", bold.clone())]);
+
+        assert_eq!(lines, vec![
+            vec![Span::raw("one")],
+            vec![Span::raw("tw"), Span::styled("o", bold.clone())],
+            vec![],
+            vec![Span::styled("three", bold)],
+        ]);
+    }
+
+    #[test]
+    fn lines_drop_carriage_return_before_newline() {
+        let lines = lines(&[Span::raw("one\r\ntwo\r"), Span::raw("\nthree\rx")]);
+
+        // only a `\r` right before `\n` is part of the line break
+        assert_eq!(plain(lines), vec!["one", "two", "three\rx"]);
+    }
+
+    #[test]
+    fn sanitize_replaces_control_characters_with_pictures() {
+        let line = sanitize(&[Span::raw("a\u{7}b\u{1b}\u{7f}")], TAB_WIDTH, &ControlChars::Placeholder);
+
+        assert_eq!(text(&line), "a\u{2407}b\u{241b}\u{2421}");
+    }
+
+    #[test]
+    fn sanitize_strips_control_characters() {
+        let line = sanitize(&[Span::raw("a\u{7}b\rc")], TAB_WIDTH, &ControlChars::Strip);
+
+        assert_eq!(text(&line), "abc");
+    }
+
+    #[test]
+    fn sanitize_expands_tabs_to_stops_across_spans() {
+        let bold = SpanStyle::new().bold();
+        let line = sanitize(&[Span::raw("ab"), Span::styled("c\td\t", bold.clone())], 4, &ControlChars::Strip);
+
+        assert_eq!(line, vec![Span::raw("ab"), Span::styled("c d   ", bold)]);
+    }
+
+    #[test]
+    fn sanitize_counts_wide_graphemes_and_placeholders_for_tab_stops() {
+        let line = sanitize(&[Span::raw("日\u{7}\tx")], 4, &ControlChars::Placeholder);
+
+        assert_eq!(text(&line), "日\u{2407} x");
+    }
+
+    #[test]
+    fn sanitize_tab_width_zero_removes_tabs() {
+        let line = sanitize(&[Span::raw("\ta\tb")], 0, &ControlChars::Placeholder);
+
+        assert_eq!(text(&line), "ab");
+    }
+
+    #[test]
+    fn sanitize_drops_spans_left_empty() {
+        let line = sanitize(&[Span::raw("a"), Span::raw("\u{7}")], TAB_WIDTH, &ControlChars::Strip);
+
+        assert_eq!(line, vec![Span::raw("a")]);
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use crate::{layout::StyleLink, span::SpanStyle, style::{Color, Style}};
+
+/// Maps semantic color names (`accent`, `surface`, `error`, ...) to concrete colors.
+#[derive(Clone, Default)]
+pub struct Theme {
+    pub name: String,
+    colors: HashMap<String, Color>,
+}
+
+impl Theme {
+    pub fn new(name: &str) -> Self {
+        Theme {
+            name: name.to_string(),
+            colors: HashMap::new(),
+        }
+    }
+
+    pub fn dark() -> Self {
+        Theme::new("dark")
+            .with("text", Color::White)
+            .with("muted", Color::BrightBlack)
+            .with("surface", Color::Black)
+            .with("accent", Color::BrightBlue)
+            .with("success", Color::Green)
+            .with("warning", Color::Yellow)
+            .with("error", Color::BrightRed)
+    }
+
+    pub fn light() -> Self {
+        Theme::new("light")
+            .with("text", Color::Black)
+            .with("muted", Color::BrightBlack)
+            .with("surface", Color::BrightWhite)
+            .with("accent", Color::Blue)
+            .with("success", Color::Green)
+            .with("warning", Color::Yellow)
+            .with("error", Color::Red)
+    }
+
+    pub fn with(mut self, name: &str, color: Color) -> Self {
+        self.set(name, color);
+        self
+    }
+
+    pub fn set(&mut self, name: &str, color: Color) {
+        self.colors.insert(name.to_string(), color);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Color> {
+        self.colors.get(name)
+    }
+
+    /// Resolves `Color::Var`, following variables that point to other variables.
+    /// Unknown names resolve to `Color::Reset`.
+    pub fn resolve(&self, color: &Color) -> Color {
+        let mut color = color.clone();
+        for _ in 0..=self.colors.len() {
+            match color {
+                Color::Var(name) => match self.colors.get(&name) {
+                    Some(c) => color = c.clone(),
+                    None => return Color::Reset,
+                },
+                _ => return color,
+            }
+        }
+        Color::Reset
+    }
+
+    pub fn apply_span_style(&self, style: &SpanStyle) -> SpanStyle {
+        SpanStyle {
+            foreground: style.foreground.as_ref().map(|c| self.resolve(c)),
+            background: style.background.as_ref().map(|c| self.resolve(c)),
+            ..style.clone()
+        }
+    }
+
+    pub fn apply(&self, style_links: &[StyleLink]) -> Vec<StyleLink> {
+        style_links
+            .iter()
+            .map(|link| StyleLink {
+                id: link.id.clone(),
+                class: link.class.clone(),
+                style: link.style.iter().map(|style| match style {
+                    Style::Foreground(color) => Style::Foreground(self.resolve(color)),
+                    Style::Background(color) => Style::Background(self.resolve(color)),
+                    _ => style.clone(),
+                }).collect(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(name: &str) -> Color {
+        Color::Var(name.to_string())
+    }
+
+    #[test]
+    fn concrete_colors_resolve_to_themselves() {
+        assert_eq!(Theme::dark().resolve(&Color::Red), Color::Red);
+    }
+
+    #[test]
+    fn variables_follow_chains() {
+        let theme = Theme::new("test")
+            .with("primary", Color::Blue)
+            .with("accent", var("primary"))
+            .with("link", var("accent"));
+        assert_eq!(theme.resolve(&var("link")), Color::Blue);
+    }
+
+    #[test]
+    fn unknown_names_resolve_to_reset() {
+        let theme = Theme::new("test").with("accent", var("missing"));
+        assert_eq!(theme.resolve(&var("nothing")), Color::Reset);
+        assert_eq!(theme.resolve(&var("accent")), Color::Reset);
+    }
+
+    #[test]
+    fn cycles_resolve_to_reset() {
+        let theme = Theme::new("test")
+            .with("a", var("b"))
+            .with("b", var("a"))
+            .with("self", var("self"));
+        assert_eq!(theme.resolve(&var("a")), Color::Reset);
+        assert_eq!(theme.resolve(&var("self")), Color::Reset);
+    }
+
+    #[test]
+    fn apply_resolves_link_colors() {
+        let theme = Theme::dark();
+        let links = theme.apply(&[StyleLink {
+            id: Some("main".to_string()),
+            class: None,
+            style: vec![Style::Foreground(var("accent")), Style::Background(var("surface"))],
+        }]);
+        assert!(matches!(links[0].style[..], [Style::Foreground(Color::BrightBlue), Style::Background(Color::Black)]));
+    }
+}